@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use web3::types::{Address, BlockId, U256};
+
+use crate::client::Asset;
+
+#[derive(Debug, Clone)]
+struct CachedAsset {
+    asset: Asset,
+    // block `asset.ts` was read at, None for seeded assets
+    supply_block: Option<BlockId>,
+}
+
+// decimals, name and symbol never change, so they are read once per token,
+// totalSupply is re-read whenever a different block is requested
+#[derive(Debug, Clone, Default)]
+pub struct AssetRegistry {
+    assets: Arc<RwLock<HashMap<Address, CachedAsset>>>,
+}
+
+impl AssetRegistry {
+    pub fn seed(&self, address: Address, d: u8, name: String, symbol: String) {
+        let asset = Asset::new(hex::encode(address), d, name, symbol, U256::zero());
+
+        self.assets.write().unwrap().insert(
+            address,
+            CachedAsset {
+                asset,
+                supply_block: None,
+            },
+        );
+    }
+
+    pub fn insert(&self, address: Address, asset: Asset, block: BlockId) {
+        self.assets.write().unwrap().insert(
+            address,
+            CachedAsset {
+                asset,
+                supply_block: Some(block),
+            },
+        );
+    }
+
+    pub fn update_supply(&self, address: Address, ts: U256, block: BlockId) {
+        if let Some(cached) = self.assets.write().unwrap().get_mut(&address) {
+            cached.asset.ts = ts;
+            cached.supply_block = Some(block);
+        }
+    }
+
+    // immutable fields only, `ts` may belong to any block
+    pub fn get(&self, address: Address) -> Option<Asset> {
+        self.assets
+            .read()
+            .unwrap()
+            .get(&address)
+            .map(|cached| cached.asset.clone())
+    }
+
+    pub fn get_at(&self, address: Address, block: BlockId) -> Option<Asset> {
+        self.assets
+            .read()
+            .unwrap()
+            .get(&address)
+            .filter(|cached| cached.supply_block == Some(block))
+            .map(|cached| cached.asset.clone())
+    }
+
+    pub fn all(&self) -> Vec<Asset> {
+        let mut assets: Vec<Asset> = self
+            .assets
+            .read()
+            .unwrap()
+            .values()
+            .map(|cached| cached.asset.clone())
+            .collect();
+        assets.sort_by(|a, b| a.t.cmp(&b.t));
+        assets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use web3::types::BlockNumber;
+
+    fn block(n: u64) -> BlockId {
+        BlockId::Number(BlockNumber::Number(n.into()))
+    }
+
+    #[test]
+    fn test_supply_is_per_block() {
+        let registry = AssetRegistry::default();
+        let address = Address::repeat_byte(1);
+        let asset = Asset::new(
+            hex::encode(address),
+            18,
+            String::from("Graviton"),
+            String::from("GTON"),
+            U256::from(100),
+        );
+
+        registry.insert(address, asset.clone(), block(10));
+        assert_eq!(registry.get_at(address, block(10)), Some(asset));
+        assert_eq!(registry.get_at(address, block(11)), None);
+
+        registry.update_supply(address, U256::from(200), block(11));
+        assert_eq!(
+            registry.get_at(address, block(11)).unwrap().ts,
+            U256::from(200)
+        );
+        assert_eq!(registry.get_at(address, block(10)), None);
+        assert_eq!(registry.get(address).unwrap().d, 18);
+    }
+
+    #[test]
+    fn test_seeded_asset_has_no_supply() {
+        let registry = AssetRegistry::default();
+        let address = Address::repeat_byte(2);
+
+        registry.seed(address, 6, String::from("USD Coin"), String::from("USDC"));
+
+        assert_eq!(registry.get(address).unwrap().symbol, "USDC");
+        assert_eq!(registry.get_at(address, block(1)), None);
+        assert_eq!(registry.all().len(), 1);
+    }
+}
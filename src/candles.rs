@@ -0,0 +1,491 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use serde::{Deserialize, Serialize};
+
+use utoipa::ToSchema;
+
+use web3::types::{Address, BlockId, BlockNumber, U256};
+
+use crate::client::Client;
+use crate::config::{PoolRole, TokenRole};
+use crate::error::Result;
+use crate::events::{IndexedEvent, PairEvent};
+use crate::indexer::Indexer;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+pub enum Resolution {
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[default]
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "1d")]
+    Day,
+}
+
+impl Resolution {
+    pub const ALL: [Resolution; 4] = [
+        Resolution::Minute,
+        Resolution::FiveMinutes,
+        Resolution::Hour,
+        Resolution::Day,
+    ];
+
+    pub fn seconds(self) -> u64 {
+        match self {
+            Resolution::Minute => 60,
+            Resolution::FiveMinutes => 300,
+            Resolution::Hour => 3600,
+            Resolution::Day => 86400,
+        }
+    }
+
+    // candles kept before the newest one, a week of minutes, a month of five
+    // minutes and a year of hours, days are kept for good
+    fn retention(self) -> Option<u64> {
+        match self {
+            Resolution::Minute => Some(7 * 1440),
+            Resolution::FiveMinutes => Some(30 * 288),
+            Resolution::Hour => Some(365 * 24),
+            Resolution::Day => None,
+        }
+    }
+}
+
+// GTON price in the other token of the pool, `volume` is GTON traded and
+// `quoteVolume` the other token, `time` is the start of the candle in seconds
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Candle {
+    pub time: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: f64,
+    pub quote_volume: f64,
+    pub trades: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Trade {
+    price: f64,
+    volume: f64,
+    quote_volume: f64,
+}
+
+impl Candle {
+    fn open(time: u64, trade: Trade) -> Candle {
+        Candle {
+            time,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.volume,
+            quote_volume: trade.quote_volume,
+            trades: 1,
+        }
+    }
+
+    fn add(&mut self, trade: Trade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.volume;
+        self.quote_volume += trade.quote_volume;
+        self.trades += 1;
+    }
+}
+
+// GTON traded against the other token of a pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Market {
+    gton_is_token0: bool,
+    gton_decimals: u8,
+    quote_decimals: u8,
+}
+
+fn net(amount_in: U256, amount_out: U256) -> U256 {
+    if amount_in > amount_out {
+        amount_in - amount_out
+    } else {
+        amount_out - amount_in
+    }
+}
+
+impl Market {
+    async fn resolve(client: &Client, role: PoolRole) -> Result<Market> {
+        let config = client.config();
+        let gton = config.token(TokenRole::Gton);
+        let quote = match role {
+            PoolRole::Base => config.token(TokenRole::Base),
+            _ => config.token(TokenRole::Usd),
+        };
+        let latest = BlockId::Number(BlockNumber::Latest);
+        let (gton_decimals, quote_decimals) = tokio::try_join!(
+            client.get_decimals(gton, latest),
+            client.get_decimals(quote, latest)
+        )?;
+
+        // UniswapV2 pairs order their tokens by address
+        Ok(Market {
+            gton_is_token0: Client::decode_addr(gton)? < Client::decode_addr(quote)?,
+            gton_decimals,
+            quote_decimals,
+        })
+    }
+
+    // the GTON side of a swap and its price, None for other events and
+    // swaps that leave either side unchanged
+    fn trade(&self, event: &PairEvent) -> Option<Trade> {
+        let (amount0, amount1) = match event {
+            PairEvent::Swap {
+                amount0_in,
+                amount1_in,
+                amount0_out,
+                amount1_out,
+                ..
+            } => (
+                net(*amount0_in, *amount0_out),
+                net(*amount1_in, *amount1_out),
+            ),
+            _ => return None,
+        };
+        let (gton, quote) = if self.gton_is_token0 {
+            (amount0, amount1)
+        } else {
+            (amount1, amount0)
+        };
+
+        let volume = Client::to_float(gton, self.gton_decimals).ok()?;
+        let quote_volume = Client::to_float(quote, self.quote_decimals).ok()?;
+        if volume == 0.0 || quote_volume == 0.0 {
+            return None;
+        }
+        Some(Trade {
+            price: quote_volume / volume,
+            volume,
+            quote_volume,
+        })
+    }
+}
+
+struct Series {
+    market: Market,
+    // key of the last swap applied
+    last: Option<(u64, u64)>,
+    candles: HashMap<Resolution, BTreeMap<u64, Candle>>,
+}
+
+impl Series {
+    fn new(market: Market) -> Series {
+        Series {
+            market,
+            last: None,
+            candles: HashMap::new(),
+        }
+    }
+
+    // events must come in chain order, the ones applied before are skipped
+    fn apply(&mut self, event: &IndexedEvent) {
+        if self.last.is_some_and(|last| event.key() <= last) {
+            return;
+        }
+        self.last = Some(event.key());
+
+        let trade = match self.market.trade(&event.event) {
+            Some(trade) => trade,
+            None => return,
+        };
+        for resolution in Resolution::ALL {
+            let time = event.timestamp - event.timestamp % resolution.seconds();
+            let candles = self.candles.entry(resolution).or_default();
+            candles
+                .entry(time)
+                .and_modify(|candle| candle.add(trade))
+                .or_insert_with(|| Candle::open(time, trade));
+
+            if let Some(kept) = resolution.retention() {
+                let oldest = time.saturating_sub(kept * resolution.seconds());
+                if candles.first_key_value().is_some_and(|(&t, _)| t < oldest) {
+                    *candles = candles.split_off(&oldest);
+                }
+            }
+        }
+    }
+}
+
+// Swap events read from the database at a time
+const EVENTS_PAGE: usize = 1000;
+
+// candles of the base and usd pools, built from the indexed Swap events and
+// extended whenever the indexer moves on
+pub struct Candles {
+    pools: Vec<(Address, PoolRole)>,
+    series: RwLock<HashMap<Address, Series>>,
+    // block the candles are complete up to
+    cursor: RwLock<Option<u64>>,
+}
+
+impl Candles {
+    pub fn new(client: &Client) -> Result<Candles> {
+        let pools = [PoolRole::Base, PoolRole::Usd]
+            .iter()
+            .map(|&role| Ok((Client::decode_addr(client.config().pool(role))?, role)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Candles {
+            pools,
+            series: RwLock::new(HashMap::new()),
+            cursor: RwLock::new(None),
+        })
+    }
+
+    pub fn has_candles(&self, pool: Address) -> bool {
+        self.pools.iter().any(|(address, _)| *address == pool)
+    }
+
+    pub fn cursor(&self) -> Option<u64> {
+        *self.cursor.read().unwrap()
+    }
+
+    // the last `limit` candles starting between `from` and `to`, oldest first
+    pub fn candles(
+        &self,
+        pool: Address,
+        resolution: Resolution,
+        from: u64,
+        to: u64,
+        limit: usize,
+    ) -> Vec<Candle> {
+        if from > to {
+            return vec![];
+        }
+        let series = self.series.read().unwrap();
+        let mut candles: Vec<Candle> = series
+            .get(&pool)
+            .and_then(|series| series.candles.get(&resolution))
+            .map(|candles| {
+                candles
+                    .range(from..=to)
+                    .rev()
+                    .take(limit)
+                    .map(|(_, c)| c.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        candles.reverse();
+        candles
+    }
+
+    async fn update(&self, client: &Client, indexer: &Indexer) -> Result<()> {
+        let cursor = indexer.cursor();
+        for &(pool, role) in &self.pools {
+            if !self.series.read().unwrap().contains_key(&pool) {
+                let market = Market::resolve(client, role).await?;
+                self.series
+                    .write()
+                    .unwrap()
+                    .insert(pool, Series::new(market));
+            }
+
+            let from = self.series.read().unwrap()[&pool]
+                .last
+                .map_or(0, |(block, _)| block);
+            let mut offset = 0;
+            loop {
+                let events = indexer
+                    .events(pool, from..=u64::MAX, Some("Swap"), offset, EVENTS_PAGE)
+                    .await?;
+                let mut series = self.series.write().unwrap();
+                let series = series.get_mut(&pool).unwrap();
+                for event in &events {
+                    series.apply(event);
+                }
+                if events.len() < EVENTS_PAGE {
+                    break;
+                }
+                offset += EVENTS_PAGE;
+            }
+        }
+        *self.cursor.write().unwrap() = cursor;
+        Ok(())
+    }
+
+    // catches up with the indexer every time it announces a new cursor
+    pub fn spawn_updater(
+        self: Arc<Self>,
+        client: Arc<Client>,
+        indexer: Arc<Indexer>,
+        network: String,
+    ) {
+        if !self.pools.iter().any(|(pool, _)| indexer.is_indexed(*pool)) {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut indexed = indexer.watch();
+            loop {
+                if let Err(e) = self.update(&client, &indexer).await {
+                    e.report(&network, "candles", "update failed");
+                }
+                if indexed.changed().await.is_err() {
+                    return;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use web3::types::H256;
+
+    // GTON with 18 decimals as token1, USDC with 6 as token0
+    const MARKET: Market = Market {
+        gton_is_token0: false,
+        gton_decimals: 18,
+        quote_decimals: 6,
+    };
+
+    fn gton(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(18)
+    }
+
+    fn usdc(amount: u64) -> U256 {
+        U256::from(amount) * U256::exp10(6)
+    }
+
+    // sells `sold` GTON for `bought` USDC
+    fn swap(timestamp: u64, log_index: u64, sold: u64, bought: u64) -> IndexedEvent {
+        IndexedEvent {
+            pool: Address::repeat_byte(0xaa),
+            block: timestamp,
+            timestamp,
+            transaction_hash: H256::zero(),
+            log_index,
+            event: PairEvent::Swap {
+                sender: Address::zero(),
+                amount0_in: 0.into(),
+                amount1_in: gton(sold),
+                amount0_out: usdc(bought),
+                amount1_out: 0.into(),
+                to: Address::zero(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_trade() {
+        let trade = MARKET.trade(&swap(0, 0, 4, 2).event).unwrap();
+        assert_eq!(
+            (trade.price, trade.volume, trade.quote_volume),
+            (0.5, 4.0, 2.0)
+        );
+
+        let buy = PairEvent::Swap {
+            sender: Address::zero(),
+            amount0_in: usdc(3),
+            amount1_in: 0.into(),
+            amount0_out: 0.into(),
+            amount1_out: gton(2),
+            to: Address::zero(),
+        };
+        assert_eq!(MARKET.trade(&buy).unwrap().price, 1.5);
+
+        let sync = PairEvent::Sync {
+            reserve0: 1.into(),
+            reserve1: 1.into(),
+        };
+        assert!(MARKET.trade(&sync).is_none());
+        assert!(MARKET.trade(&swap(0, 0, 0, 2).event).is_none());
+    }
+
+    #[test]
+    fn test_series() {
+        let mut series = Series::new(MARKET);
+        for event in [
+            swap(60, 0, 1, 1),
+            swap(70, 0, 1, 3),
+            swap(80, 0, 2, 1),
+            swap(130, 0, 1, 2),
+            swap(4000, 0, 1, 4),
+        ] {
+            series.apply(&event);
+        }
+        // applied again after a restart of the updater
+        series.apply(&swap(70, 0, 1, 3));
+
+        let minutes = &series.candles[&Resolution::Minute];
+        assert_eq!(minutes.len(), 3);
+        assert_eq!(
+            minutes[&60],
+            Candle {
+                time: 60,
+                open: 1.0,
+                high: 3.0,
+                low: 0.5,
+                close: 0.5,
+                volume: 4.0,
+                quote_volume: 5.0,
+                trades: 3,
+            }
+        );
+        let hours = &series.candles[&Resolution::Hour];
+        assert_eq!(hours[&0].trades, 4);
+        assert_eq!(hours[&3600].close, 4.0);
+        assert_eq!(series.candles[&Resolution::Day][&0].trades, 5);
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut series = Series::new(MARKET);
+        let week = 7 * 86400;
+        for timestamp in [0, 60, week, week + 60] {
+            series.apply(&swap(timestamp, 0, 1, 1));
+        }
+
+        let minutes: Vec<u64> = series.candles[&Resolution::Minute]
+            .keys()
+            .copied()
+            .collect();
+        assert_eq!(minutes, vec![60, week, week + 60]);
+        assert_eq!(series.candles[&Resolution::Hour].len(), 2);
+        assert_eq!(series.candles[&Resolution::Day].len(), 2);
+    }
+
+    #[test]
+    fn test_candles() {
+        let pool = Address::repeat_byte(0xaa);
+        let mut series = Series::new(MARKET);
+        for minute in 0..10 {
+            series.apply(&swap(minute * 60, 0, 1, minute + 1));
+        }
+        let candles = Candles {
+            pools: vec![(pool, PoolRole::Usd)],
+            series: RwLock::new(HashMap::from([(pool, series)])),
+            cursor: RwLock::new(None),
+        };
+
+        assert!(candles.has_candles(pool));
+        assert!(!candles.has_candles(Address::zero()));
+        let times: Vec<u64> = candles
+            .candles(pool, Resolution::Minute, 60, 300, 3)
+            .iter()
+            .map(|c| c.time)
+            .collect();
+        assert_eq!(times, vec![180, 240, 300]);
+        assert_eq!(
+            candles.candles(pool, Resolution::FiveMinutes, 0, u64::MAX, 10)[1].open,
+            6.0
+        );
+        assert!(candles
+            .candles(pool, Resolution::Minute, 300, 60, 10)
+            .is_empty());
+    }
+}
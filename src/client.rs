@@ -2,27 +2,43 @@ use std::str::FromStr;
 use std::string::ToString;
 
 use std::fs;
-use std::io;
 use std::path::Path;
-use std::sync::{Arc, RwLock};
-use std::time::Duration;
 
 use bigdecimal::{BigDecimal, ToPrimitive};
 
 use serde::{Deserialize, Serialize};
 
-use either::Either;
+use utoipa::ToSchema;
 
-use futures_retry::{FutureRetry, RetryPolicy};
-use hex;
-use web3::{contract::Contract, contract::Options, types::Address, types::U256, *};
+use either::Either;
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+use futures::TryFutureExt;
+
+use web3::{
+    contract::Contract,
+    types::Address,
+    types::U256,
+    types::{Block, BlockId, BlockNumber, Filter, Log, H256},
+    *,
+};
+
+use crate::assets::AssetRegistry;
+use crate::config::{
+    NetworkConfig, PoolRole, QuorumConfig, RetryConfig, TokenRole, UpstreamConfig,
+};
+use crate::error::{checked_div, finite, PwError, Result};
+use crate::multicall::{Batch, BatchResults, Call, Multicall, MULTICALL3};
+use crate::retry::{retry, LogsError};
+use crate::twap::{self, CumulativePrices, Twap};
+use crate::upstream::{UpstreamStatus, Upstreams};
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct Asset {
     pub t: String,
     pub d: u8,
     pub name: String,
     pub symbol: String,
+    #[schema(value_type = String)]
     pub ts: U256,
 }
 
@@ -38,273 +54,1103 @@ impl Asset {
     }
 }
 
-fn handle_network_err<E>(_e: E) -> RetryPolicy<io::Error> {
-    RetryPolicy::WaitRetry(Duration::from_millis(70))
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolReserves {
+    pub pool: String,
+    pub token0: String,
+    pub token1: String,
+    pub reserve0: f64,
+    pub reserve1: f64,
+    pub block_timestamp_last: u32,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolPrice {
+    pub pool: String,
+    pub base: String,
+    pub quote: String,
+    pub price: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolLiquidity {
+    pub pool: String,
+    pub token0: String,
+    pub token1: String,
+    pub balance0: f64,
+    pub balance1: f64,
+    // twice the value of whichever side is the usd or base token of the network
+    pub usd: Option<f64>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolShare {
+    pub pool: String,
+    pub account: String,
+    pub balance: f64,
+    pub supply: f64,
+    pub share: f64,
+    pub amount0: f64,
+    pub amount1: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Quote {
+    pub router: String,
+    pub path: Vec<String>,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    // last token of the path per first token, fees included
+    pub execution_price: f64,
+    // product of the spot prices of every hop
+    pub mid_price: f64,
+    pub price_impact: f64,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FactoryPair {
+    pub index: u64,
+    pub pair: String,
+    pub token0: Asset,
+    pub token1: Asset,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FactoryPairs {
+    pub factory: String,
+    pub total: u64,
+    pub offset: u64,
+    pub pairs: Vec<FactoryPair>,
 }
 
-async fn try_fetch_token_data(
-    token_contract: &Contract<transports::Http>,
+async fn execute<T: Transport>(
+    config: &RetryConfig,
+    multicall: &Multicall<T>,
+    batch: &Batch,
+    block: BlockId,
+) -> Result<BatchResults> {
+    retry(config, move || batch.execute(multicall, block)).await
+}
+
+async fn try_fetch_token_data<T: Transport>(
+    multicall: &Multicall<T>,
+    token_contract: &Contract<T>,
+    block: BlockId,
 ) -> std::result::Result<Asset, web3::contract::Error> {
-    let result = tokio::try_join!(
-        token_contract.query("decimals", (), None, Options::default(), None),
-        token_contract.query("name", (), None, Options::default(), None),
-        token_contract.query("symbol", (), None, Options::default(), None),
-        token_contract.query("totalSupply", (), None, Options::default(), None),
-    );
-    match result {
-        Ok(v) => {
-            let (d, name, symbol, ts) = v;
-            Ok(Asset::new(
-                hex::encode(token_contract.address()).to_string(),
-                d,
-                name,
-                symbol,
-                ts,
-            ))
-        }
-        Err(e) => Err(e),
-    }
+    let mut batch = Batch::default();
+    let d = batch.add(Call::new(token_contract, "decimals", ()));
+    let name = batch.add(Call::new(token_contract, "name", ()));
+    let symbol = batch.add(Call::new(token_contract, "symbol", ()));
+    let ts = batch.add(Call::new(token_contract, "totalSupply", ()));
+
+    let results = batch.execute(multicall, block).await?;
+    Ok(Asset::new(
+        hex::encode(token_contract.address()),
+        results.get(d)?,
+        results.get(name)?,
+        results.get(symbol)?,
+        results.get(ts)?,
+    ))
+}
+
+async fn fetch_token_data<T: Transport>(
+    config: &RetryConfig,
+    multicall: &Multicall<T>,
+    token_contract: &Contract<T>,
+    block: BlockId,
+) -> Result<Asset> {
+    retry(config, move || {
+        try_fetch_token_data(multicall, token_contract, block)
+    })
+    .await
+}
+
+type RawReserves = (Address, Address, (U256, U256, u32));
+
+async fn try_fetch_reserves<T: Transport>(
+    multicall: &Multicall<T>,
+    pair_contract: &Contract<T>,
+    block: BlockId,
+) -> std::result::Result<RawReserves, web3::contract::Error> {
+    let mut batch = Batch::default();
+    let token0 = batch.add(Call::new(pair_contract, "token0", ()));
+    let token1 = batch.add(Call::new(pair_contract, "token1", ()));
+    let reserves = batch.add(Call::new(pair_contract, "getReserves", ()));
+
+    let results = batch.execute(multicall, block).await?;
+    Ok((
+        results.get(token0)?,
+        results.get(token1)?,
+        results.get(reserves)?,
+    ))
+}
+
+async fn fetch_reserves<T: Transport>(
+    config: &RetryConfig,
+    multicall: &Multicall<T>,
+    pair_contract: &Contract<T>,
+    block: BlockId,
+) -> Result<RawReserves> {
+    retry(config, move || {
+        try_fetch_reserves(multicall, pair_contract, block)
+    })
+    .await
+}
+
+type RawCumulativePrices = (U256, U256, (U256, U256, u32));
+
+async fn try_fetch_cumulative_prices<T: Transport>(
+    multicall: &Multicall<T>,
+    pair_contract: &Contract<T>,
+    block: BlockId,
+) -> std::result::Result<RawCumulativePrices, web3::contract::Error> {
+    let mut batch = Batch::default();
+    let price0 = batch.add(Call::new(pair_contract, "price0CumulativeLast", ()));
+    let price1 = batch.add(Call::new(pair_contract, "price1CumulativeLast", ()));
+    let reserves = batch.add(Call::new(pair_contract, "getReserves", ()));
+
+    let results = batch.execute(multicall, block).await?;
+    Ok((
+        results.get(price0)?,
+        results.get(price1)?,
+        results.get(reserves)?,
+    ))
 }
 
-async fn fetch_token_data(token_contract: &Contract<transports::Http>) -> Asset {
-    let (result, _) = FutureRetry::new(
-        move || try_fetch_token_data(token_contract),
-        handle_network_err,
-    )
+async fn fetch_cumulative_prices<T: Transport>(
+    config: &RetryConfig,
+    multicall: &Multicall<T>,
+    pair_contract: &Contract<T>,
+    block: BlockId,
+) -> Result<RawCumulativePrices> {
+    retry(config, move || {
+        try_fetch_cumulative_prices(multicall, pair_contract, block)
+    })
     .await
-    .unwrap();
-    result
+}
+
+// every on-chain input of the GC POL and peg computations
+struct PolInputs {
+    wftm_price: f64,
+    ftm_gton_liq: f64,
+    ftm_gton_gc_pol_lp: f64,
+    ftm_gton_lp: f64,
+    usdc_gton_liq: f64,
+    usdc_gton_gc_pol_lp: f64,
+    usdc_gton_lp: f64,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BlockTag {
+    #[default]
+    Latest,
+    Safe,
+    Finalized,
+}
+
+impl BlockTag {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlockTag::Latest => "latest",
+            BlockTag::Safe => "safe",
+            BlockTag::Finalized => "finalized",
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Props {
-    pub node_rpc: String, //
+    // tried in priority order, see upstream.rs
+    pub upstreams: Vec<UpstreamConfig>,
+    pub config: NetworkConfig,
 }
 
 #[derive(Clone)]
 #[allow(dead_code)]
 pub struct Client {
     props: Props,
-    web3: Web3<web3::transports::Http>,
+    web3: Web3<Upstreams>,
     uniswap_pair_abi: Vec<u8>,
+    uniswap_factory_abi: Vec<u8>,
+    uniswap_router_abi: Vec<u8>,
     erc20_abi: Vec<u8>,
+    multicall_abi: Vec<u8>,
+    multicall: Multicall,
+    assets: AssetRegistry,
 }
 
-struct InternalToken(&'static str, u8);
-
 impl Client {
-    pub fn decode_addr(address: &str) -> Address {
-        let address = if address[0..2] == String::from("0x") {
-            &address[2..]
-        } else {
-            address
-        };
-        let dc = hex::decode(address).unwrap();
-        Address::from_slice(dc.as_slice())
+    pub fn decode_addr(address: &str) -> Result<Address> {
+        let hex_address = address.strip_prefix("0x").unwrap_or(address);
+        match hex::decode(hex_address) {
+            Ok(dc) if dc.len() == Address::len_bytes() => Ok(Address::from_slice(dc.as_slice())),
+            _ => Err(PwError::InvalidAddress(address.to_string())),
+        }
+    }
+
+    pub fn encode_addr(address: Address) -> String {
+        format!("0x{}", hex::encode(address))
+    }
+
+    pub fn to_float(amount: U256, decimals: u8) -> Result<f64> {
+        let r = BigDecimal::from_str(amount.to_string().as_str())
+            .ok()
+            .and_then(|r| r.to_f64())
+            .ok_or(PwError::NotFinite("token amount"))?;
+        let mult = 10f64.powf(decimals as f64);
+
+        finite(r / mult, "token amount")
+    }
+
+    // parses a positive decimal `amount` into token units
+    fn from_float(amount: &str, decimals: u8) -> Result<U256> {
+        let invalid = || PwError::InvalidAmount(amount.to_string());
+        let parsed = BigDecimal::from_str(amount).map_err(|_| invalid())?;
+        let mult =
+            BigDecimal::from_str(format!("1e{}", decimals).as_str()).map_err(|_| invalid())?;
+
+        let units = (parsed * mult).with_scale(0);
+        if units <= BigDecimal::from(0) {
+            return Err(invalid());
+        }
+        U256::from_dec_str(units.to_string().as_str()).map_err(|_| invalid())
     }
 
     pub fn build_contract(
         &self,
         path: Either<&str, Vec<u8>>,
         address: &str,
-    ) -> Contract<web3::transports::Http> {
-        // let endpoint = std::env::var("RPC").unwrap();
-        let transport = transports::Http::new(self.props.node_rpc.as_str()).unwrap();
-        let web3 = Web3::new(transport);
-
+    ) -> Result<Contract<Upstreams>> {
         let file_abi = match path {
             Either::Left(path) => {
-                let file_abi = fs::read(Path::new(path)).unwrap();
-                file_abi
+                fs::read(Path::new(path)).map_err(|e| PwError::Io(path.to_string(), e))?
             }
             Either::Right(file_abi) => file_abi,
         };
 
-        let decoded_address = Self::decode_addr(address);
-        Contract::from_json(web3.eth(), decoded_address, file_abi.as_slice()).unwrap()
+        let decoded_address = Self::decode_addr(address)?;
+        let contract = Contract::from_json(self.web3.eth(), decoded_address, file_abi.as_slice())
+            .map_err(web3::contract::Error::from)?;
+        Ok(contract)
     }
 
-    pub async fn new(props: Props) -> Self {
-        let transport = web3::transports::Http::new(props.node_rpc.as_str()).unwrap();
+    async fn read_abi(path: &str) -> Result<Vec<u8>> {
+        tokio::fs::read(Path::new(path))
+            .await
+            .map_err(|e| PwError::Io(path.to_string(), e))
+    }
+
+    pub async fn new(props: Props) -> Result<Self> {
+        let transport =
+            Upstreams::new(&props.config.name, &props.upstreams, &props.config.health).await;
+        transport.spawn_health_checks();
         let web3 = web3::Web3::new(transport);
 
-        let uniswap_pair_abi = tokio::fs::read(Path::new("./abi/UniswapV2Pair.json"))
-            .await
-            .unwrap();
-        let erc20_abi = tokio::fs::read(Path::new("./abi/ERC20.json"))
-            .await
-            .unwrap();
+        let uniswap_pair_abi = Self::read_abi("./abi/UniswapV2Pair.json").await?;
+        let uniswap_factory_abi = Self::read_abi("./abi/UniswapV2Factory.json").await?;
+        let uniswap_router_abi = Self::read_abi("./abi/UniswapV2Router02.json").await?;
+        let erc20_abi = Self::read_abi("./abi/ERC20.json").await?;
+        let multicall_abi = Self::read_abi("./abi/Multicall3.json").await?;
+        let multicall = Multicall::new(
+            web3.clone(),
+            multicall_abi.as_slice(),
+            Self::decode_addr(props.config.multicall.as_deref().unwrap_or(MULTICALL3))?,
+        )?;
+
+        let assets = AssetRegistry::default();
+        for token in &props.config.tokens {
+            if let (Some(name), Some(symbol), Some(d)) =
+                (&token.name, &token.symbol, token.decimals)
+            {
+                assets.seed(
+                    Self::decode_addr(&token.address)?,
+                    d,
+                    name.clone(),
+                    symbol.clone(),
+                );
+            }
+        }
 
-        Client {
+        let mut client = Client {
             web3,
             props,
             uniswap_pair_abi,
+            uniswap_factory_abi,
+            uniswap_router_abi,
             erc20_abi,
+            multicall_abi,
+            multicall,
+            assets,
+        };
+        client.resolve_pools().await?;
+        Ok(client)
+    }
+
+    // fills in the address of every pool declared as (factory, tokenA, tokenB)
+    async fn resolve_pools(&mut self) -> Result<()> {
+        let mut batch = Batch::default();
+        let mut pairs = Vec::new();
+        for (i, pool) in self.props.config.pools.iter().enumerate() {
+            if let (None, Some(factory), Some([token_a, token_b])) =
+                (&pool.address, &pool.factory, &pool.tokens)
+            {
+                let factory_contract =
+                    self.build_contract(Either::Right(self.uniswap_factory_abi.clone()), factory)?;
+                let call = Call::new(
+                    &factory_contract,
+                    "getPair",
+                    (Self::decode_addr(token_a)?, Self::decode_addr(token_b)?),
+                );
+                pairs.push((i, batch.add(call)));
+            }
+        }
+        if pairs.is_empty() {
+            return Ok(());
         }
+
+        let results = execute(
+            &self.props.config.retry,
+            &self.multicall,
+            &batch,
+            BlockId::Number(BlockNumber::Latest),
+        )
+        .await?;
+        for (i, pair) in pairs {
+            let address: Address = results.get(pair)?;
+            let pool = &mut self.props.config.pools[i];
+            if address.is_zero() {
+                let [token_a, token_b] = pool.tokens.clone().unwrap_or_default();
+                return Err(PwError::PairNotFound(token_a, token_b));
+            }
+            pool.address = Some(Self::encode_addr(address));
+        }
+        Ok(())
     }
 
-    // FG_SPI_LP = { tid: '0x25f5b3840d414a21c4fc46d21699e54d48f75fdd', dec: 18 }
-    // F_TOKEN = { tid: '0x21be370d5312f44cb42ce377bc9b8a0cef1a4c83', dec: 18 }
-    // G_TOKEN = { tid: '0xC1Be9a4D5D45BeeACAE296a7BD5fADBfc14602C4', dec: 18 }
-    // U_TOKEN = { tid: '0x04068da6c83afcfa0e13ba15a6696662335d5b75', dec: 6 }
-    // FU_SPO_LP = { tid: '0x2b4c76d0dc16be1c31d4c1dc53bf9b45987fc75c', dec: 18 }
-    // UG_SPO_LP = { tid: '0xb9b452A71Dd1cfB4952d90e03bf701A6C7Ae263b', dec: 18 }
-
-    const FG_SPI_LP: &'static InternalToken =
-        &InternalToken("0x25f5b3840d414a21c4fc46d21699e54d48f75fdd", 18);
-    const F_TOKEN: &'static InternalToken =
-        &InternalToken("0x21be370d5312f44cb42ce377bc9b8a0cef1a4c83", 18);
-    const G_TOKEN: &'static InternalToken =
-        &InternalToken("0xC1Be9a4D5D45BeeACAE296a7BD5fADBfc14602C4", 18);
-    const U_TOKEN: &'static InternalToken =
-        &InternalToken("0x04068da6c83afcfa0e13ba15a6696662335d5b75", 6);
-    const FU_SPO_LP: &'static InternalToken =
-        &InternalToken("0x2b4c76d0dc16be1c31d4c1dc53bf9b45987fc75c", 18);
-    const UG_SPO_LP: &'static InternalToken =
-        &InternalToken("0xb9b452A71Dd1cfB4952d90e03bf701A6C7Ae263b", 18);
-
-    const GC_ACCS: &'static [&'static str; 3] = &[
-        "0xB3D22267E7260ec6c3931d50D215ABa5Fd54506a",
-        "0xbb652A9FAc95B5203f44aa3492200b6aE6aD84e0",
-        "0xd196B496425Be880BA63AcE90C60258b9A52b044",
-    ];
-
-    async fn try_fetch_balance_of(
-        token_contract: Contract<web3::transports::Http>,
-        holder: &str,
-    ) -> std::result::Result<U256, web3::contract::Error> {
-        let r = token_contract
-            .query(
-                "balanceOf",
-                Self::decode_addr(holder),
-                None,
-                Options::default(),
-                None,
+    // composite metrics resolve a single block up front, so every read they
+    // make is pinned to the same chain state
+    pub async fn resolve_block(
+        &self,
+        block: Option<u64>,
+        tag: Option<BlockTag>,
+    ) -> Result<BlockId> {
+        if let Some(number) = block {
+            return Ok(BlockId::Number(BlockNumber::Number(number.into())));
+        }
+
+        let tag = tag.unwrap_or_default();
+        // a websocket or IPC upstream announces new blocks, saving the round trip
+        if let (BlockTag::Latest, Some(number)) = (tag, self.web3.transport().latest_head()) {
+            return Ok(BlockId::Number(BlockNumber::Number(number.into())));
+        }
+        let block = self.fetch_block_by_tag(tag).await?;
+        let number = block
+            .number
+            .ok_or_else(|| PwError::BlockNotFound(tag.as_str().to_string()))?;
+        Ok(BlockId::Number(BlockNumber::Number(number)))
+    }
+
+    // web3 BlockNumber has no safe/finalized variants, so the tag is passed as is
+    async fn fetch_block_by_tag(&self, tag: BlockTag) -> Result<Block<H256>> {
+        let transport = self.web3.transport();
+        let result = retry(&self.props.config.retry, move || {
+            transport.execute(
+                "eth_getBlockByNumber",
+                vec![serde_json::json!(tag.as_str()), serde_json::json!(false)],
             )
-            .await;
-        r
+        })
+        .await?;
+        if result.is_null() {
+            return Err(PwError::BlockNotFound(tag.as_str().to_string()));
+        }
+        serde_json::from_value(result).map_err(|e| web3::Error::Decoder(e.to_string()).into())
+    }
+
+    pub fn get_upstreams(&self) -> Vec<UpstreamStatus> {
+        self.web3.transport().status()
+    }
+
+    pub fn watch_heads(&self) -> tokio::sync::watch::Receiver<Option<u64>> {
+        self.web3.transport().watch_heads()
+    }
+
+    pub fn config(&self) -> &NetworkConfig {
+        &self.props.config
+    }
+
+    // names of the ABIs read at startup that don't parse
+    // ABIs that don't parse or lack a function the reads depend on, aggregate3
+    // carries every batched read
+    pub fn invalid_abis(&self) -> Vec<&'static str> {
+        [
+            ("UniswapV2Pair", &self.uniswap_pair_abi, "getReserves"),
+            ("UniswapV2Factory", &self.uniswap_factory_abi, "getPair"),
+            (
+                "UniswapV2Router02",
+                &self.uniswap_router_abi,
+                "getAmountsOut",
+            ),
+            ("ERC20", &self.erc20_abi, "balanceOf"),
+            ("Multicall3", &self.multicall_abi, "aggregate3"),
+        ]
+        .into_iter()
+        .filter(|(_, abi, function)| {
+            web3::ethabi::Contract::load(abi.as_slice())
+                .map_or(true, |contract| contract.function(function).is_err())
+        })
+        .map(|(name, _, _)| name)
+        .collect()
+    }
+
+    pub fn pair_abi(&self) -> &[u8] {
+        &self.uniswap_pair_abi
+    }
+
+    // every configured pool, factory pools are resolved by `new`
+    pub fn pools(&self) -> Result<Vec<Address>> {
+        self.props
+            .config
+            .pools
+            .iter()
+            .filter_map(|pool| pool.address.as_deref())
+            .map(Self::decode_addr)
+            .collect()
+    }
+
+    // a range the node refuses is not retried, the indexer asks for a smaller one
+    pub async fn get_logs(&self, filter: Filter) -> Result<Vec<Log>> {
+        let eth = self.web3.eth();
+        retry(&self.props.config.retry, move || {
+            eth.logs(filter.clone()).map_err(LogsError)
+        })
+        .await
+    }
+
+    pub async fn get_block_timestamp(&self, number: u64) -> Result<u64> {
+        let block = self.fetch_block(BlockId::Number(number.into())).await?;
+        Ok(block.timestamp.as_u64())
     }
 
     /** RPC FUNCTIONS **/
-    async fn get_erc20token_balance<'a>(&self, token: &'a str, holder: &'a str) -> f64 {
-        let token_contract =
-            self.build_contract(Either::Right(self.uniswap_pair_abi.clone()), token);
-        let token_data = fetch_token_data(&token_contract).await;
+    // token metadata is served from the registry, only totalSupply is read per block
+    pub async fn get_asset(&self, token: &str, block: BlockId) -> Result<Asset> {
+        let address = Self::decode_addr(token)?;
+        if let Some(asset) = self.assets.get_at(address, block) {
+            return Ok(asset);
+        }
 
-        let result = Self::try_fetch_balance_of(token_contract.clone(), holder)
-            .await
-            .unwrap();
+        let token_contract = self.build_contract(Either::Right(self.erc20_abi.clone()), token)?;
+        match self.assets.get(address) {
+            Some(mut asset) => {
+                let mut batch = Batch::default();
+                let ts = batch.add(Call::new(&token_contract, "totalSupply", ()));
+
+                let results =
+                    execute(&self.props.config.retry, &self.multicall, &batch, block).await?;
+                asset.ts = results.get(ts)?;
+                self.assets.update_supply(address, asset.ts, block);
+                Ok(asset)
+            }
+            None => {
+                let asset = fetch_token_data(
+                    &self.props.config.retry,
+                    &self.multicall,
+                    &token_contract,
+                    block,
+                )
+                .await?;
+                self.assets.insert(address, asset.clone(), block);
+                Ok(asset)
+            }
+        }
+    }
 
-        let r = result as U256;
-        let r = BigDecimal::from_str(r.to_string().as_str()).unwrap();
-        let r = r.to_f64().unwrap();
-        let mult = 10f64.powf(token_data.d as f64);
+    pub fn get_assets(&self) -> Vec<Asset> {
+        self.assets.all()
+    }
 
-        r / mult
+    pub async fn get_decimals(&self, token: &str, block: BlockId) -> Result<u8> {
+        match self.assets.get(Self::decode_addr(token)?) {
+            Some(asset) => Ok(asset.d),
+            None => Ok(self.get_asset(token, block).await?.d),
+        }
     }
 
-    async fn get_erc20token_supply<'a>(&self, token: &'a str) -> f64 {
+    async fn get_erc20token_balances(
+        &self,
+        token: &str,
+        holders: &[&str],
+        block: BlockId,
+    ) -> Result<Vec<f64>> {
         let token_contract =
-            self.build_contract(Either::Right(self.uniswap_pair_abi.clone()), token);
-        let token_data = fetch_token_data(&token_contract).await;
+            self.build_contract(Either::Right(self.uniswap_pair_abi.clone()), token)?;
+
+        let mut batch = Batch::default();
+        let mut balances = Vec::new();
+        for holder in holders {
+            balances.push(batch.add(Call::new(
+                &token_contract,
+                "balanceOf",
+                Self::decode_addr(holder)?,
+            )));
+        }
 
-        let r = token_data.ts as U256;
-        let r = BigDecimal::from_str(r.to_string().as_str()).unwrap();
-        let r = r.to_f64().unwrap();
-        let mult = 10f64.powf(token_data.d as f64);
+        let (results, d) = tokio::try_join!(
+            execute(&self.props.config.retry, &self.multicall, &batch, block),
+            self.get_decimals(token, block)
+        )?;
 
-        r / mult
+        balances
+            .into_iter()
+            .map(|balance| Self::to_float(results.get(balance)?, d))
+            .collect()
     }
 
-    pub async fn get_wftm_price(&self) -> f64 {
-        let u_token = Self::U_TOKEN.clone();
-        let fu_spo_lp = Self::FU_SPO_LP.clone();
-        let f_token = Self::F_TOKEN.clone();
+    async fn get_erc20token_balance(
+        &self,
+        token: &str,
+        holder: &str,
+        block: BlockId,
+    ) -> Result<f64> {
+        Ok(self
+            .get_erc20token_balances(token, &[holder], block)
+            .await?[0])
+    }
+
+    async fn get_erc20token_supply(&self, token: &str, block: BlockId) -> Result<f64> {
+        let token_data = self.get_asset(token, block).await?;
 
-        println!("{:?}", u_token.0);
-        println!("{:?}", fu_spo_lp.0);
-        println!("{:?}", f_token.0);
+        Self::to_float(token_data.ts, token_data.d)
+    }
+
+    // reserves are read through getReserves instead of balanceOf of the pair,
+    // so tokens donated to the pair without a sync do not skew the price
+    pub async fn get_pool_reserves(&self, pool: &str, block: BlockId) -> Result<PoolReserves> {
+        let pair_contract =
+            self.build_contract(Either::Right(self.uniswap_pair_abi.clone()), pool)?;
+        let (token0, token1, (reserve0, reserve1, block_timestamp_last)) = fetch_reserves(
+            &self.props.config.retry,
+            &self.multicall,
+            &pair_contract,
+            block,
+        )
+        .await?;
 
-        let amount_u = self.get_erc20token_balance(u_token.0, fu_spo_lp.0).await;
-        let amount_f = self.get_erc20token_balance(f_token.0, fu_spo_lp.0).await;
+        let (token0_data, token1_data) = self.fetch_pool_tokens(token0, token1, block).await?;
 
-        amount_u / amount_f
+        Ok(PoolReserves {
+            pool: Self::encode_addr(pair_contract.address()),
+            token0: Self::encode_addr(token0),
+            token1: Self::encode_addr(token1),
+            reserve0: Self::to_float(reserve0, token0_data.d)?,
+            reserve1: Self::to_float(reserve1, token1_data.d)?,
+            block_timestamp_last,
+        })
     }
 
-    pub async fn get_wftm_gton_gc_pool_lp(&'static self) -> f64 {
-        let fg_spi_lp = Self::FG_SPI_LP.clone();
-        let gc_accs = Self::GC_ACCS.clone();
+    // price of `base` denominated in the other token of the pool
+    pub async fn get_pool_price(&self, pool: &str, base: &str, block: BlockId) -> Result<f64> {
+        Ok(self
+            .get_pool_spot_price(pool, Some(base), block)
+            .await?
+            .price)
+    }
 
-        let n = gc_accs.len();
+    // spot price of `base` (token0 if unset) in the other token of any pair
+    pub async fn get_pool_spot_price(
+        &self,
+        pool: &str,
+        base: Option<&str>,
+        block: BlockId,
+    ) -> Result<PoolPrice> {
+        let reserves = self.get_pool_reserves(pool, block).await?;
+        let base = match base {
+            Some(base) => Self::encode_addr(Self::decode_addr(base)?),
+            None => reserves.token0.clone(),
+        };
 
-        let mut handles = Vec::new();
-        let sum_of = Arc::new(RwLock::new(0.0));
+        let (quote, price) = if reserves.token0 == base {
+            (
+                reserves.token1,
+                checked_div(reserves.reserve1, reserves.reserve0, "pool price")?,
+            )
+        } else if reserves.token1 == base {
+            (
+                reserves.token0,
+                checked_div(reserves.reserve0, reserves.reserve1, "pool price")?,
+            )
+        } else {
+            return Err(PwError::TokenNotInPool(base, reserves.pool));
+        };
 
-        for i in 0..n {
-            let sum_of_c = Arc::clone(&sum_of);
+        Ok(PoolPrice {
+            pool: reserves.pool,
+            base,
+            quote,
+            price,
+        })
+    }
 
-            let handle = tokio::task::spawn(async move {
-                let x = gc_accs[i];
-                let r = self.get_erc20token_balance(fg_spi_lp.0, x).await;
-                *sum_of_c.write().unwrap() += r;
-            });
+    pub fn default_router(&self) -> Option<&str> {
+        self.props.config.router.as_deref()
+    }
 
-            handles.push(handle);
+    // quotes a swap of `amount` through `path` with getAmountsOut when `exact_in`,
+    // otherwise getAmountsIn for `amount` of the last token of the path
+    pub async fn get_quote(
+        &self,
+        router: &str,
+        path: &[&str],
+        amount: &str,
+        exact_in: bool,
+        block: BlockId,
+    ) -> Result<Quote> {
+        if path.len() < 2 {
+            return Err(PwError::InvalidPath(String::from(
+                "a path needs at least two tokens",
+            )));
         }
+        let router_contract =
+            self.build_contract(Either::Right(self.uniswap_router_abi.clone()), router)?;
+        let tokens = path
+            .iter()
+            .map(|token| Self::decode_addr(token))
+            .collect::<Result<Vec<Address>>>()?;
+
+        let decimals =
+            futures::future::try_join_all(path.iter().map(|token| self.get_decimals(token, block)))
+                .await?;
+        let (decimals_in, decimals_out) = (decimals[0], decimals[decimals.len() - 1]);
+        let amount = Self::from_float(amount, if exact_in { decimals_in } else { decimals_out })?;
+
+        let mut batch = Batch::default();
+        let function = if exact_in {
+            "getAmountsOut"
+        } else {
+            "getAmountsIn"
+        };
+        let amounts = batch.add(Call::new(
+            &router_contract,
+            function,
+            (amount, tokens.clone()),
+        ));
+        let factory = batch.add(Call::new(&router_contract, "factory", ()));
 
-        for h in handles {
-            h.await.unwrap();
+        let results = execute(&self.props.config.retry, &self.multicall, &batch, block)
+            .await
+            .map_err(|e| Self::quote_error(path, e))?;
+        let amounts: Vec<U256> = results.get(amounts)?;
+        let factory: Address = results.get(factory)?;
+
+        let factory_contract = self.build_contract(
+            Either::Right(self.uniswap_factory_abi.clone()),
+            &Self::encode_addr(factory),
+        )?;
+        let mut batch = Batch::default();
+        let pairs: Vec<usize> = tokens
+            .windows(2)
+            .map(|hop| batch.add(Call::new(&factory_contract, "getPair", (hop[0], hop[1]))))
+            .collect();
+        let results = execute(&self.props.config.retry, &self.multicall, &batch, block).await?;
+        let pairs = pairs
+            .into_iter()
+            .map(|i| results.get(i))
+            .collect::<std::result::Result<Vec<Address>, _>>()?;
+        if let Some(hop) = pairs.iter().position(|pair| pair.is_zero()) {
+            return Err(PwError::PairNotFound(
+                path[hop].to_string(),
+                path[hop + 1].to_string(),
+            ));
         }
 
-        Arc::try_unwrap(sum_of).unwrap().into_inner().unwrap()
+        let prices = futures::future::try_join_all(pairs.iter().zip(path).map(|(pair, base)| {
+            let pair = Self::encode_addr(*pair);
+            async move { self.get_pool_spot_price(&pair, Some(base), block).await }
+        }))
+        .await?;
+        let mid_price: f64 = prices.into_iter().map(|price| price.price).product();
+
+        let amount_in = Self::to_float(amounts[0], decimals_in)?;
+        let amount_out = Self::to_float(amounts[amounts.len() - 1], decimals_out)?;
+        let execution_price = checked_div(amount_out, amount_in, "execution price")?;
+
+        Ok(Quote {
+            router: Self::encode_addr(router_contract.address()),
+            path: tokens.into_iter().map(Self::encode_addr).collect(),
+            amount_in,
+            amount_out,
+            execution_price,
+            mid_price,
+            price_impact: 1.0 - checked_div(execution_price, mid_price, "price impact")?,
+        })
+    }
+
+    // USD value of `amount` of `token`, known for the usd and base tokens only
+    async fn get_usd_value(&self, token: &str, amount: f64, block: BlockId) -> Result<Option<f64>> {
+        let config = &self.props.config;
+        let token = Self::decode_addr(token)?;
+        let is = |role| Self::decode_addr(config.token(role)).ok() == Some(token);
+
+        if is(TokenRole::Usd) {
+            Ok(Some(amount))
+        } else if is(TokenRole::Base) {
+            Ok(Some(amount * self.get_wftm_price(block).await?))
+        } else {
+            Ok(None)
+        }
     }
 
-    pub async fn get_usdc_gton_gc_pool_lp(&'static self) -> f64 {
-        let ug_spo_lp = Self::UG_SPO_LP.clone();
-        let gc_accs = Self::GC_ACCS.clone();
+    // token balances of any pair, the same way base-liquidity and usd-liquidity are computed
+    pub async fn get_pool_liquidity(&self, pool: &str, block: BlockId) -> Result<PoolLiquidity> {
+        let (reserves, balance0, balance1) = self.fetch_pool_balances(pool, block).await?;
+
+        let usd = match self
+            .get_usd_value(&reserves.token0, balance0, block)
+            .await?
+        {
+            Some(value) => Some(2.0 * value),
+            None => self
+                .get_usd_value(&reserves.token1, balance1, block)
+                .await?
+                .map(|value| 2.0 * value),
+        };
 
-        let n = gc_accs.len();
+        Ok(PoolLiquidity {
+            pool: reserves.pool,
+            token0: reserves.token0,
+            token1: reserves.token1,
+            balance0,
+            balance1,
+            usd,
+        })
+    }
 
-        let mut handles = Vec::new();
-        let sum_of = Arc::new(RwLock::new(0.0));
+    async fn fetch_pool_balances(
+        &self,
+        pool: &str,
+        block: BlockId,
+    ) -> Result<(PoolReserves, f64, f64)> {
+        let reserves = self.get_pool_reserves(pool, block).await?;
+
+        let (balance0, balance1) = tokio::try_join!(
+            self.get_erc20token_balance(&reserves.token0, &reserves.pool, block),
+            self.get_erc20token_balance(&reserves.token1, &reserves.pool, block)
+        )?;
+        Ok((reserves, balance0, balance1))
+    }
 
-        for i in 0..n {
-            let sum_of_c = Arc::clone(&sum_of);
+    pub async fn get_pool_lp_supply(&self, pool: &str, block: BlockId) -> Result<f64> {
+        self.get_erc20token_supply(pool, block).await
+    }
 
-            let handle = tokio::task::spawn(async move {
-                let x = gc_accs[i];
-                let r = self.get_erc20token_balance(ug_spo_lp.0, x).await;
-                *sum_of_c.write().unwrap() += r;
-            });
+    // share of the LP supply held by `account` and the pool balances it redeems for
+    pub async fn get_pool_share(
+        &self,
+        pool: &str,
+        account: &str,
+        block: BlockId,
+    ) -> Result<PoolShare> {
+        let ((reserves, balance0, balance1), balance, supply) = tokio::try_join!(
+            self.fetch_pool_balances(pool, block),
+            self.get_erc20token_balance(pool, account, block),
+            self.get_erc20token_supply(pool, block)
+        )?;
+        let share = if supply > 0.0 { balance / supply } else { 0.0 };
+
+        Ok(PoolShare {
+            pool: reserves.pool,
+            account: Self::encode_addr(Self::decode_addr(account)?),
+            balance,
+            supply,
+            share,
+            amount0: share * balance0,
+            amount1: share * balance1,
+        })
+    }
 
-            handles.push(handle);
+    async fn fetch_pool_tokens(
+        &self,
+        token0: Address,
+        token1: Address,
+        block: BlockId,
+    ) -> Result<(Asset, Asset)> {
+        let (token0, token1) = (Self::encode_addr(token0), Self::encode_addr(token1));
+
+        tokio::try_join!(
+            self.get_asset(&token0, block),
+            self.get_asset(&token1, block)
+        )
+    }
+
+    // pages through allPairs of a UniswapV2 factory, `limit` pairs from `offset`
+    pub async fn get_factory_pairs(
+        &self,
+        factory: &str,
+        offset: u64,
+        limit: u64,
+        block: BlockId,
+    ) -> Result<FactoryPairs> {
+        let factory_contract =
+            self.build_contract(Either::Right(self.uniswap_factory_abi.clone()), factory)?;
+
+        let mut batch = Batch::default();
+        let length = batch.add(Call::new(&factory_contract, "allPairsLength", ()));
+        let total: U256 = execute(&self.props.config.retry, &self.multicall, &batch, block)
+            .await?
+            .get(length)?;
+        let total = total.as_u64();
+
+        let indexes: Vec<u64> = (offset..total.min(offset.saturating_add(limit))).collect();
+        let mut batch = Batch::default();
+        let pairs: Vec<usize> = indexes
+            .iter()
+            .map(|i| batch.add(Call::new(&factory_contract, "allPairs", U256::from(*i))))
+            .collect();
+        let results = execute(&self.props.config.retry, &self.multicall, &batch, block).await?;
+        let pairs = pairs
+            .into_iter()
+            .map(|i| results.get(i))
+            .collect::<std::result::Result<Vec<Address>, _>>()?;
+
+        let mut batch = Batch::default();
+        let mut tokens = Vec::new();
+        for pair in &pairs {
+            let pair_contract = self.build_contract(
+                Either::Right(self.uniswap_pair_abi.clone()),
+                &Self::encode_addr(*pair),
+            )?;
+            tokens.push((
+                batch.add(Call::new(&pair_contract, "token0", ())),
+                batch.add(Call::new(&pair_contract, "token1", ())),
+            ));
         }
+        let results = execute(&self.props.config.retry, &self.multicall, &batch, block).await?;
+
+        let pairs = futures::future::try_join_all(indexes.into_iter().zip(pairs).zip(tokens).map(
+            |((index, pair), (token0, token1))| {
+                let results = &results;
+                async move {
+                    let (token0, token1) = self
+                        .fetch_pool_tokens(results.get(token0)?, results.get(token1)?, block)
+                        .await?;
+
+                    Ok::<FactoryPair, PwError>(FactoryPair {
+                        index,
+                        pair: Self::encode_addr(pair),
+                        token0,
+                        token1,
+                    })
+                }
+            },
+        ))
+        .await?;
+
+        Ok(FactoryPairs {
+            factory: Self::encode_addr(factory_contract.address()),
+            total,
+            offset,
+            pairs,
+        })
+    }
 
-        for h in handles {
-            h.await.unwrap();
+    async fn fetch_block(&self, block: BlockId) -> Result<Block<H256>> {
+        let eth = self.web3.eth();
+        let result = retry(&self.props.config.retry, move || eth.block(block)).await?;
+        result.ok_or_else(|| PwError::BlockNotFound(format!("{:?}", block)))
+    }
+
+    // the router reverts on paths without liquidity, reverts are not retried.
+    // any other node error was already turned into PwError::Node
+    fn quote_error(path: &[&str], e: PwError) -> PwError {
+        match e {
+            PwError::Contract(web3::contract::Error::Api(web3::Error::Rpc(e))) => {
+                PwError::NoQuote(path.join(","), e.message)
+            }
+            e => e,
         }
+    }
 
-        Arc::try_unwrap(sum_of).unwrap().into_inner().unwrap()
+    fn block_number(block: &Block<H256>) -> Result<u64> {
+        block
+            .number
+            .map(|number| number.as_u64())
+            .ok_or_else(|| PwError::BlockNotFound(String::from("pending")))
     }
 
-    pub async fn get_ftm_gton_liq(&self) -> f64 {
-        let fg_spi_lp = Self::FG_SPI_LP.clone();
-        let f_token = Self::F_TOKEN.clone();
+    // number of blocks sampled to estimate the average block time
+    const BLOCK_TIME_SAMPLE: u64 = 1000;
+
+    // estimates the block mined `window` seconds before `latest`
+    // from the average block time of the last BLOCK_TIME_SAMPLE blocks
+    async fn get_block_before(&self, latest: &Block<H256>, window: u64) -> Result<u64> {
+        let latest_number = Self::block_number(latest)?;
+        let sample_number = latest_number.saturating_sub(Self::BLOCK_TIME_SAMPLE);
+        let sample = self
+            .fetch_block(BlockId::Number(sample_number.into()))
+            .await?;
+
+        let elapsed = latest.timestamp.saturating_sub(sample.timestamp).as_u64() as f64;
+        let block_time = checked_div(
+            elapsed,
+            (latest_number - sample_number) as f64,
+            "block time",
+        )?;
+        let window_blocks = checked_div(window as f64, block_time, "twap window")?.ceil() as u64;
+
+        Ok(latest_number.saturating_sub(window_blocks.max(1)))
+    }
 
-        let wftm_price = self.get_wftm_price().await;
-        let f_token_balance_of_fg_spi_lp =
-            self.get_erc20token_balance(f_token.0, fg_spi_lp.0).await;
+    async fn get_cumulative_prices(
+        &self,
+        pair_contract: &Contract<Upstreams>,
+        block: &Block<H256>,
+    ) -> Result<CumulativePrices> {
+        let number = Self::block_number(block)?;
+        let (price0_cumulative_last, price1_cumulative_last, reserves) = fetch_cumulative_prices(
+            &self.props.config.retry,
+            &self.multicall,
+            pair_contract,
+            BlockId::Number(number.into()),
+        )
+        .await?;
+
+        Ok(CumulativePrices::new(
+            number,
+            block.timestamp.as_u64(),
+            price0_cumulative_last,
+            price1_cumulative_last,
+            reserves,
+        ))
+    }
+
+    // time weighted average price of `base` in the other token of the pool
+    // over `window` seconds up to `block`, sampled from price{0,1}CumulativeLast
+    pub async fn get_pool_twap(
+        &self,
+        pool: &str,
+        base: &str,
+        window: u64,
+        block: BlockId,
+    ) -> Result<Twap> {
+        let pair_contract =
+            self.build_contract(Either::Right(self.uniswap_pair_abi.clone()), pool)?;
+
+        let latest = self.fetch_block(block).await?;
+        let start_number = self.get_block_before(&latest, window).await?;
+        let start = self
+            .fetch_block(BlockId::Number(start_number.into()))
+            .await?;
+
+        let (start_prices, end_prices, (token0, token1, _)) = tokio::try_join!(
+            self.get_cumulative_prices(&pair_contract, &start),
+            self.get_cumulative_prices(&pair_contract, &latest),
+            fetch_reserves(
+                &self.props.config.retry,
+                &self.multicall,
+                &pair_contract,
+                block
+            )
+        )?;
+        let (token0_data, token1_data) = self.fetch_pool_tokens(token0, token1, block).await?;
+
+        let (price0, price1) = twap::average_prices(&start_prices, &end_prices)
+            .ok_or(PwError::DivisionByZero("twap"))?;
+        let decimals_diff = token0_data.d as i32 - token1_data.d as i32;
+
+        let base = Self::encode_addr(Self::decode_addr(base)?);
+        let (token0, token1) = (Self::encode_addr(token0), Self::encode_addr(token1));
+        let (quote, price) = if token0 == base {
+            (token1, price0 * 10f64.powi(decimals_diff))
+        } else if token1 == base {
+            (token0, price1 * 10f64.powi(-decimals_diff))
+        } else {
+            return Err(PwError::TokenNotInPool(
+                base,
+                Self::encode_addr(pair_contract.address()),
+            ));
+        };
 
-        2.0 * wftm_price * f_token_balance_of_fg_spi_lp
+        Ok(Twap {
+            pool: Self::encode_addr(pair_contract.address()),
+            base,
+            quote,
+            price,
+            window,
+            from_block: start_prices.block,
+            to_block: end_prices.block,
+            from_timestamp: start_prices.timestamp,
+            to_timestamp: end_prices.timestamp,
+        })
     }
 
-    pub async fn get_usdc_gton_liq(&self) -> f64 {
-        let g_token = Self::G_TOKEN.clone();
-        let ug_spo_lp = Self::UG_SPO_LP.clone();
+    pub async fn get_wftm_price(&self, block: BlockId) -> Result<f64> {
+        self.get_pool_price(
+            self.props.config.pool(PoolRole::Quote),
+            self.props.config.token(TokenRole::Base),
+            block,
+        )
+        .await
+    }
+
+    pub async fn get_wftm_twap(&self, window: u64, block: BlockId) -> Result<Twap> {
+        self.get_pool_twap(
+            self.props.config.pool(PoolRole::Quote),
+            self.props.config.token(TokenRole::Base),
+            window,
+            block,
+        )
+        .await
+    }
+
+    pub async fn get_wftm_gton_gc_pool_lp(&self, block: BlockId) -> Result<f64> {
+        self.get_erc20token_balances(
+            self.props.config.pool(PoolRole::Base),
+            &self.props.config.gc_accounts(),
+            block,
+        )
+        .await
+        .map(|balances| balances.into_iter().sum())
+    }
+
+    pub async fn get_usdc_gton_gc_pool_lp(&self, block: BlockId) -> Result<f64> {
+        self.get_erc20token_balances(
+            self.props.config.pool(PoolRole::Usd),
+            &self.props.config.gc_accounts(),
+            block,
+        )
+        .await
+        .map(|balances| balances.into_iter().sum())
+    }
+
+    pub async fn get_ftm_gton_liq(&self, block: BlockId) -> Result<f64> {
+        let fg_spi_lp = self.props.config.pool(PoolRole::Base);
+        let f_token = self.props.config.token(TokenRole::Base);
 
-        let balance_of = self.get_erc20token_balance(g_token.0, ug_spo_lp.0).await;
+        let (wftm_price, f_token_balance_of_fg_spi_lp) = tokio::try_join!(
+            self.get_wftm_price(block),
+            self.get_erc20token_balance(f_token, fg_spi_lp, block)
+        )?;
 
-        2.0 * balance_of
+        Ok(2.0 * wftm_price * f_token_balance_of_fg_spi_lp)
+    }
+
+    pub async fn get_usdc_gton_liq(&self, block: BlockId) -> Result<f64> {
+        let g_token = self.props.config.token(TokenRole::Gton);
+        let ug_spo_lp = self.props.config.pool(PoolRole::Usd);
+
+        let balance_of = self
+            .get_erc20token_balance(g_token, ug_spo_lp, block)
+            .await?;
+
+        Ok(2.0 * balance_of)
     }
 
     // def getFtmGtonLP(self):
@@ -314,46 +1160,164 @@ impl Client {
 
     //   return self.apiFtmSanGetTokenSupply(FG_SPI_LP[tid], FG_SPI_LP[dec])
 
-    pub async fn get_ftm_gton_lp(&self) -> f64 {
-        let fg_spi_lp = Self::FG_SPI_LP.clone();
+    pub async fn get_ftm_gton_lp(&self, block: BlockId) -> Result<f64> {
+        let fg_spi_lp = self.props.config.pool(PoolRole::Base);
 
-        self.get_erc20token_supply(fg_spi_lp.0).await
+        self.get_erc20token_supply(fg_spi_lp, block).await
     }
 
-    pub async fn get_usdc_gton_lp(&self) -> f64 {
-        let ug_spo_lp = Self::UG_SPO_LP.clone();
+    pub async fn get_usdc_gton_lp(&self, block: BlockId) -> Result<f64> {
+        let ug_spo_lp = self.props.config.pool(PoolRole::Usd);
 
-        self.get_erc20token_supply(ug_spo_lp.0).await
+        self.get_erc20token_supply(ug_spo_lp, block).await
     }
 
-    // def getGCpol(self):
-    // return sum([self.getFtmGtonLiq()*self.getFtmGtonGCpolLP()/self.getFtmGtonLP(), self.getUsdGtonLiq()*self.getUsdGtonGCpolLP()/self.getUsdGtonLP()])
+    // sends `batch` to `quorum.size` upstreams and returns the result at least
+    // `quorum.min_agree` of them returned, upstreams outvoted are counted in their status
+    async fn execute_quorum(
+        &self,
+        quorum: &QuorumConfig,
+        batch: &Batch,
+        block: BlockId,
+    ) -> Result<BatchResults> {
+        let transport = self.web3.transport();
+        let members = transport.quorum_members(quorum.size);
+        let multicalls: Vec<Multicall> = members
+            .iter()
+            .map(|&i| self.multicall.with_transport(transport.pinned(i)))
+            .collect();
+        let results = futures::future::join_all(
+            multicalls
+                .iter()
+                .map(|multicall| execute(&self.props.config.retry, multicall, batch, block)),
+        )
+        .await;
+
+        let mut groups: Vec<(BatchResults, Vec<usize>)> = Vec::new();
+        let mut last_error = None;
+        for (i, result) in members.into_iter().zip(results) {
+            match result {
+                Ok(r) => match groups.iter_mut().find(|(g, _)| g.agrees_with(&r)) {
+                    Some((_, agreeing)) => agreeing.push(i),
+                    None => groups.push((r, vec![i])),
+                },
+                Err(e) => last_error = Some(e),
+            }
+        }
+        groups.sort_by_key(|(_, agreeing)| std::cmp::Reverse(agreeing.len()));
+        for (_, outvoted) in groups.iter().skip(1) {
+            for &i in outvoted {
+                transport.record_disagreement(i);
+            }
+        }
 
-    pub async fn get_gc_pol(&'static self) -> f64 {
-        let (
-            ftm_gton_liq,
-            ftm_gton_gc_pol_lp,
-            ftm_gton_lp,
-            usdc_gton_liq,
-            usdc_gton_lp,
-            usdc_gton_gc_pol_lp,
-        ): (f64, f64, f64, f64, f64, f64) = tokio::join!(
-            self.get_ftm_gton_liq(),
-            self.get_wftm_gton_gc_pool_lp(),
-            self.get_ftm_gton_lp(),
-            self.get_usdc_gton_liq(),
-            self.get_usdc_gton_lp(),
-            self.get_usdc_gton_gc_pool_lp()
+        match groups.into_iter().next() {
+            Some((r, agreeing)) if agreeing.len() >= quorum.min_agree => Ok(r),
+            Some((_, agreeing)) => Err(PwError::NoQuorum(agreeing.len(), quorum.min_agree)),
+            None => Err(last_error.unwrap_or(PwError::NoQuorum(0, quorum.min_agree))),
+        }
+    }
+
+    // reads everything POL and the pegs depend on in a single multicall
+    async fn fetch_pol_inputs(&self, block: BlockId) -> Result<PolInputs> {
+        let pair =
+            |address| self.build_contract(Either::Right(self.uniswap_pair_abi.clone()), address);
+        let token = |address| self.build_contract(Either::Right(self.erc20_abi.clone()), address);
+
+        let (fg_spi_lp, fu_spo_lp, ug_spo_lp) = (
+            pair(self.props.config.pool(PoolRole::Base))?,
+            pair(self.props.config.pool(PoolRole::Quote))?,
+            pair(self.props.config.pool(PoolRole::Usd))?,
+        );
+        let (f_token, g_token) = (
+            token(self.props.config.token(TokenRole::Base))?,
+            token(self.props.config.token(TokenRole::Gton))?,
         );
 
-        let sum_of = vec![
-            ftm_gton_liq * ftm_gton_gc_pol_lp / ftm_gton_lp,
-            usdc_gton_liq * usdc_gton_gc_pol_lp / usdc_gton_lp,
+        let mut batch = Batch::default();
+        let fu_token0 = batch.add(Call::new(&fu_spo_lp, "token0", ()));
+        let fu_reserves = batch.add(Call::new(&fu_spo_lp, "getReserves", ()));
+
+        let f_balance_of_fg = batch.add(Call::new(&f_token, "balanceOf", fg_spi_lp.address()));
+        let g_balance_of_ug = batch.add(Call::new(&g_token, "balanceOf", ug_spo_lp.address()));
+
+        let fg_supply = batch.add(Call::new(&fg_spi_lp, "totalSupply", ()));
+        let ug_supply = batch.add(Call::new(&ug_spo_lp, "totalSupply", ()));
+
+        let (mut fg_gc, mut ug_gc) = (Vec::new(), Vec::new());
+        for acc in self.props.config.gc_accounts() {
+            let acc = Self::decode_addr(acc)?;
+            fg_gc.push(batch.add(Call::new(&fg_spi_lp, "balanceOf", acc)));
+            ug_gc.push(batch.add(Call::new(&ug_spo_lp, "balanceOf", acc)));
+        }
+
+        // these inputs drive the peg, with a quorum configured no single node is trusted
+        let results = async {
+            match &self.props.config.quorum {
+                Some(quorum) => self.execute_quorum(quorum, &batch, block).await,
+                None => execute(&self.props.config.retry, &self.multicall, &batch, block).await,
+            }
+        };
+        let (r, f_decimals, g_decimals, u_decimals, fg_decimals, ug_decimals) = tokio::try_join!(
+            results,
+            self.get_decimals(self.props.config.token(TokenRole::Base), block),
+            self.get_decimals(self.props.config.token(TokenRole::Gton), block),
+            self.get_decimals(self.props.config.token(TokenRole::Usd), block),
+            self.get_decimals(self.props.config.pool(PoolRole::Base), block),
+            self.get_decimals(self.props.config.pool(PoolRole::Usd), block)
+        )?;
+        let amount =
+            |index: usize, decimals: u8| -> Result<f64> { Self::to_float(r.get(index)?, decimals) };
+        let sum = |indexes: Vec<usize>, decimals: u8| -> Result<f64> {
+            indexes.into_iter().map(|i| amount(i, decimals)).sum()
+        };
+
+        let (reserve0, reserve1, _): (U256, U256, u32) = r.get(fu_reserves)?;
+        let fu_token0: Address = r.get(fu_token0)?;
+        let (reserve_f, reserve_u) = if fu_token0 == f_token.address() {
+            (reserve0, reserve1)
+        } else {
+            (reserve1, reserve0)
+        };
+        let wftm_price = checked_div(
+            Self::to_float(reserve_u, u_decimals)?,
+            Self::to_float(reserve_f, f_decimals)?,
+            "base price",
+        )?;
+
+        Ok(PolInputs {
+            wftm_price,
+            ftm_gton_liq: 2.0 * wftm_price * amount(f_balance_of_fg, f_decimals)?,
+            ftm_gton_gc_pol_lp: sum(fg_gc, fg_decimals)?,
+            ftm_gton_lp: amount(fg_supply, fg_decimals)?,
+            usdc_gton_liq: 2.0 * amount(g_balance_of_ug, g_decimals)?,
+            usdc_gton_gc_pol_lp: sum(ug_gc, ug_decimals)?,
+            usdc_gton_lp: amount(ug_supply, ug_decimals)?,
+        })
+    }
+
+    // def getGCpol(self):
+    // return sum([self.getFtmGtonLiq()*self.getFtmGtonGCpolLP()/self.getFtmGtonLP(), self.getUsdGtonLiq()*self.getUsdGtonGCpolLP()/self.getUsdGtonLP()])
+
+    fn gc_pol(inputs: &PolInputs) -> Result<f64> {
+        Ok(vec![
+            checked_div(
+                inputs.ftm_gton_liq * inputs.ftm_gton_gc_pol_lp,
+                inputs.ftm_gton_lp,
+                "base pool POL",
+            )?,
+            checked_div(
+                inputs.usdc_gton_liq * inputs.usdc_gton_gc_pol_lp,
+                inputs.usdc_gton_lp,
+                "usd pool POL",
+            )?,
         ]
         .into_iter()
-        .sum();
+        .sum())
+    }
 
-        sum_of
+    pub async fn get_gc_pol(&self, block: BlockId) -> Result<f64> {
+        Self::gc_pol(&self.fetch_pol_inputs(block).await?)
     }
 
     // # lib api
@@ -379,47 +1343,100 @@ impl Client {
         gc_bias: f64,
         gc_max_p: f64,
         gc_max_l: f64,
-    ) -> f64 {
-        f64::max(gc_floor, gc_bias + (gc_max_p * _pol / gc_max_l))
+    ) -> Result<f64> {
+        Self::pw_model(_pol, gc_floor, gc_bias, gc_max_p, gc_max_l)
+    }
+
+    fn pw_model(
+        _pol: f64,
+        gc_floor: f64,
+        gc_bias: f64,
+        gc_max_p: f64,
+        gc_max_l: f64,
+    ) -> Result<f64> {
+        for value in [_pol, gc_floor, gc_bias, gc_max_p, gc_max_l] {
+            finite(value, "pw model parameter")?;
+        }
+
+        Ok(f64::max(
+            gc_floor,
+            gc_bias + checked_div(gc_max_p * _pol, gc_max_l, "pw model")?,
+        ))
     }
 
-    pub async fn get_gc_pw_current_peg_usd(&'static self) -> f64 {
-        self.get_pw_model_with_pol_mln(
-            self.get_gc_pol().await / 10f64.powf(6.0),
-            2.05,
-            1.7,
-            600.0,
-            550.0,
+    // the peg in usd of the configured model at `pol`
+    fn current_peg_usd(&self, pol: f64) -> Result<f64> {
+        let model = &self.props.config.model;
+
+        Self::pw_model(
+            pol / 10f64.powf(6.0),
+            model.gc_floor,
+            model.gc_bias,
+            model.gc_max_p,
+            model.gc_max_l,
         )
-        .await
     }
 
-    pub async fn get_gc_pw_current_peg_ftm(&'static self) -> f64 {
-        let (gc_pol, wftm_price) = tokio::join!(self.get_gc_pol(), self.get_wftm_price());
+    pub async fn get_gc_pw_current_peg_usd(&self, block: BlockId) -> Result<f64> {
+        self.current_peg_usd(self.get_gc_pol(block).await?)
+    }
 
-        let pw_model_pol = self
-            .get_pw_model_with_pol_mln(gc_pol / 10f64.powf(6.0), 2.05, 1.7, 600.0, 550.0)
-            .await;
+    pub async fn get_gc_pw_current_peg_ftm(&self, block: BlockId) -> Result<f64> {
+        let inputs = self.fetch_pol_inputs(block).await?;
+        let peg_usd = self.current_peg_usd(Self::gc_pol(&inputs)?)?;
 
-        pw_model_pol / wftm_price
+        checked_div(peg_usd, inputs.wftm_price, "base peg")
     }
 
-    pub async fn get_gton_usdc_price(&'static self) -> f64 {
-        let (amount_u, amount_g) = tokio::join!(
-            self.get_erc20token_balance(Self::U_TOKEN.0, Self::UG_SPO_LP.0),
-            self.get_erc20token_balance(Self::G_TOKEN.0, Self::UG_SPO_LP.0)
-        );
+    // POL, the usd peg and the base peg from a single read of their inputs
+    pub async fn get_gc_pol_and_pegs(&self, block: BlockId) -> Result<(f64, f64, f64)> {
+        let inputs = self.fetch_pol_inputs(block).await?;
+        let pol = Self::gc_pol(&inputs)?;
+        let peg_usd = self.current_peg_usd(pol)?;
+
+        Ok((
+            pol,
+            peg_usd,
+            checked_div(peg_usd, inputs.wftm_price, "base peg")?,
+        ))
+    }
 
-        amount_u / amount_g
+    pub async fn get_gton_usdc_price(&self, block: BlockId) -> Result<f64> {
+        self.get_pool_price(
+            self.props.config.pool(PoolRole::Usd),
+            self.props.config.token(TokenRole::Gton),
+            block,
+        )
+        .await
     }
 
-    pub async fn get_gton_wftm_price(&'static self) -> f64 {
-        let (amount_g, amount_f) = tokio::join!(
-            self.get_erc20token_balance(Self::G_TOKEN.0, Self::FG_SPI_LP.0),
-            self.get_erc20token_balance(Self::F_TOKEN.0, Self::FG_SPI_LP.0)
-        );
+    pub async fn get_gton_wftm_price(&self, block: BlockId) -> Result<f64> {
+        self.get_pool_price(
+            self.props.config.pool(PoolRole::Base),
+            self.props.config.token(TokenRole::Gton),
+            block,
+        )
+        .await
+    }
 
-        amount_f / amount_g
+    pub async fn get_gton_usdc_twap(&self, window: u64, block: BlockId) -> Result<Twap> {
+        self.get_pool_twap(
+            self.props.config.pool(PoolRole::Usd),
+            self.props.config.token(TokenRole::Gton),
+            window,
+            block,
+        )
+        .await
+    }
+
+    pub async fn get_gton_wftm_twap(&self, window: u64, block: BlockId) -> Result<Twap> {
+        self.get_pool_twap(
+            self.props.config.pool(PoolRole::Base),
+            self.props.config.token(TokenRole::Gton),
+            window,
+            block,
+        )
+        .await
     }
 }
 
@@ -427,26 +1444,191 @@ impl Client {
 mod tests {
     use super::*;
 
+    use crate::config::Config;
+
+    #[test]
+    fn test_from_float() {
+        assert_eq!(
+            Client::from_float("1.5", 18).unwrap(),
+            U256::from(15) * U256::exp10(17)
+        );
+        assert_eq!(Client::from_float("0.1", 6).unwrap(), U256::from(100000));
+        assert!(matches!(
+            Client::from_float("0.0000001", 6),
+            Err(PwError::InvalidAmount(_))
+        ));
+        assert!(matches!(
+            Client::from_float("-1", 6),
+            Err(PwError::InvalidAmount(_))
+        ));
+        assert!(matches!(
+            Client::from_float("abc", 6),
+            Err(PwError::InvalidAmount(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_float() {
+        assert_eq!(
+            Client::to_float(U256::from(15) * U256::exp10(17), 18).unwrap(),
+            1.5
+        );
+        assert_eq!(Client::to_float(U256::zero(), 6).unwrap(), 0.0);
+        assert_eq!(
+            Client::to_float(U256::MAX, 0).unwrap(),
+            U256::MAX.to_string().parse::<f64>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pw_model() {
+        assert_eq!(Client::pw_model(2.0, 1.0, 0.5, 3.0, 4.0).unwrap(), 2.0);
+        assert_eq!(Client::pw_model(0.0, 1.0, 0.5, 3.0, 4.0).unwrap(), 1.0);
+        assert!(matches!(
+            Client::pw_model(2.0, 1.0, 0.5, 3.0, 0.0),
+            Err(PwError::DivisionByZero(_))
+        ));
+        for i in 0..5 {
+            let mut args = [2.0, 1.0, 0.5, 3.0, 4.0];
+            args[i] = f64::INFINITY;
+            let [pol, floor, bias, max_p, max_l] = args;
+            assert!(
+                matches!(
+                    Client::pw_model(pol, floor, bias, max_p, max_l),
+                    Err(PwError::NotFinite(_))
+                ),
+                "parameter {}",
+                i
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_invalid_abis() {
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new("http://127.0.0.1:1")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        assert!(client.invalid_abis().is_empty());
+
+        let without_aggregate3 = Client {
+            multicall_abi: client.erc20_abi.clone(),
+            ..client
+        };
+        assert_eq!(without_aggregate3.invalid_abis(), vec!["Multicall3"]);
+    }
+
+    #[test]
+    fn test_quote_error() {
+        let rpc_error = |code: i64, message: &str| {
+            let e = serde_json::json!({ "code": code, "message": message });
+            PwError::from(web3::contract::Error::Api(web3::Error::Rpc(
+                serde_json::from_value(e).unwrap(),
+            )))
+        };
+        let path = ["0xa", "0xb"];
+
+        assert!(matches!(
+            Client::quote_error(&path, rpc_error(3, "execution reverted: INSUFFICIENT_LIQUIDITY")),
+            PwError::NoQuote(p, _) if p == "0xa,0xb"
+        ));
+        assert!(matches!(
+            Client::quote_error(&path, rpc_error(-32000, "execution reverted")),
+            PwError::NoQuote(_, _)
+        ));
+        assert!(matches!(
+            Client::quote_error(&path, rpc_error(-32601, "method not found")),
+            PwError::Node(web3::Error::Rpc(_))
+        ));
+        assert!(matches!(
+            Client::quote_error(&path, PwError::Timeout(std::time::Duration::from_secs(1))),
+            PwError::Timeout(_)
+        ));
+    }
+
     #[tokio::test]
     async fn test_get_wftm_price() {
         let client = Client::new(Props {
-            node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+            upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
         })
-        .await;
-        println!("get_wftm_price: {:?}", client.get_wftm_price().await);
+        .await
+        .unwrap();
+        println!(
+            "get_wftm_price: {:?}",
+            client
+                .get_wftm_price(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_pool_reserves() {
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        println!(
+            "get_pool_reserves: {:?}",
+            client
+                .get_pool_reserves(
+                    "0x25f5b3840d414a21c4fc46d21699e54d48f75fdd",
+                    client.resolve_block(None, None).await.unwrap()
+                )
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_wftm_twap() {
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        println!(
+            "get_wftm_twap: {:?}",
+            client
+                .get_wftm_twap(1800, client.resolve_block(None, None).await.unwrap())
+                .await
+        );
     }
 
     #[tokio::test]
     async fn test_get_wftm_gton_gc_pool_lp() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
         println!(
             "get_wftm_gton_gc_pool_lp: {:?}",
-            client.get_wftm_gton_gc_pool_lp().await
+            client
+                .get_wftm_gton_gc_pool_lp(client.resolve_block(None, None).await.unwrap())
+                .await
         );
     }
 
@@ -454,13 +1636,20 @@ mod tests {
     async fn test_get_usdc_gton_gc_pool_lp() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
         println!(
             "get_usdc_gton_gc_pool_lp: {:?}",
-            client.get_usdc_gton_gc_pool_lp().await
+            client
+                .get_usdc_gton_gc_pool_lp(client.resolve_block(None, None).await.unwrap())
+                .await
         );
     }
 
@@ -468,68 +1657,125 @@ mod tests {
     async fn test_get_ftm_gton_liq() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
-        println!("get_ftm_gton_liq: {:?}", client.get_ftm_gton_liq().await);
+        println!(
+            "get_ftm_gton_liq: {:?}",
+            client
+                .get_ftm_gton_liq(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
     }
 
     #[tokio::test]
     async fn test_get_usdc_gton_liq() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
-        println!("get_usdc_gton_liq: {:?}", client.get_usdc_gton_liq().await);
+        println!(
+            "get_usdc_gton_liq: {:?}",
+            client
+                .get_usdc_gton_liq(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
     }
 
     #[tokio::test]
     async fn test_get_ftm_gton_lp() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
-        println!("get_ftm_gton_lp: {:?}", client.get_ftm_gton_lp().await);
+        println!(
+            "get_ftm_gton_lp: {:?}",
+            client
+                .get_ftm_gton_lp(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
     }
 
     #[tokio::test]
     async fn test_get_usdc_gton_lp() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
-        println!("get_usdc_gton_lp: {:?}", client.get_usdc_gton_lp().await);
+        println!(
+            "get_usdc_gton_lp: {:?}",
+            client
+                .get_usdc_gton_lp(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
     }
 
     #[tokio::test]
     async fn test_get_gc_pol() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
-        println!("get_gc_pol: {:?}", client.get_gc_pol().await);
+        println!(
+            "get_gc_pol: {:?}",
+            client
+                .get_gc_pol(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
     }
 
     #[tokio::test]
     async fn test_get_gton_usdc_price() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
         println!(
             "get_gton_usdc_price: {:?}",
-            client.get_gton_usdc_price().await
+            client
+                .get_gton_usdc_price(client.resolve_block(None, None).await.unwrap())
+                .await
         );
     }
 
@@ -537,13 +1783,20 @@ mod tests {
     async fn test_get_gton_wftm_price() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
         println!(
             "get_gton_wftm_price: {:?}",
-            client.get_gton_wftm_price().await
+            client
+                .get_gton_wftm_price(client.resolve_block(None, None).await.unwrap())
+                .await
         );
     }
 
@@ -551,13 +1804,20 @@ mod tests {
     async fn test_get_gc_pw_current_peg_usd() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
         println!(
             "get_gc_pw_current_peg_usd: {:?}",
-            client.get_gc_pw_current_peg_usd().await
+            client
+                .get_gc_pw_current_peg_usd(client.resolve_block(None, None).await.unwrap())
+                .await
         );
     }
 
@@ -565,13 +1825,84 @@ mod tests {
     async fn test_get_gc_pw_current_peg_ftm() {
         let client = Box::leak(Box::new(
             Client::new(Props {
-                node_rpc: String::from("https://rpcapi-tracing.fantom.network"),
+                upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+                config: Config::from_file("./config.toml")
+                    .unwrap()
+                    .default_network()
+                    .clone(),
             })
-            .await,
+            .await
+            .unwrap(),
         ));
         println!(
             "get_gc_pw_current_peg_ftm: {:?}",
-            client.get_gc_pw_current_peg_ftm().await
+            client
+                .get_gc_pw_current_peg_ftm(client.resolve_block(None, None).await.unwrap())
+                .await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_factory_pairs() {
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        let block = client.resolve_block(None, None).await.unwrap();
+        let pairs = client
+            .get_factory_pairs("0x152eE697f2E276fA89E96742e9bB9aB1F2E61bE3", 0, 5, block)
+            .await
+            .unwrap();
+        assert_eq!(pairs.pairs.len(), 5);
+        println!("get_factory_pairs: {:?}", pairs);
+    }
+
+    #[tokio::test]
+    async fn test_get_pool_share() {
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        let block = client.resolve_block(None, None).await.unwrap();
+        let pool = client.props.config.pool(PoolRole::Base);
+        let account = client.props.config.gc_accounts()[0];
+        println!(
+            "get_pool_share: {:?}",
+            client.get_pool_share(pool, account, block).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_quote() {
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new("https://rpcapi-tracing.fantom.network")],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        let block = client.resolve_block(None, None).await.unwrap();
+        let path = [
+            client.props.config.token(TokenRole::Usd),
+            client.props.config.token(TokenRole::Base),
+        ];
+        println!(
+            "get_quote: {:?}",
+            client
+                .get_quote(client.default_router().unwrap(), &path, "100", true, block)
+                .await
         );
     }
 }
@@ -0,0 +1,841 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use utoipa::{OpenApi, ToSchema};
+
+use thiserror::Error;
+
+use web3::types::Address;
+
+use crate::openapi::ApiDoc;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("failed to read config {0}: {1}")]
+    Io(String, #[source] io::Error),
+    #[error("failed to parse config {0}: {1}")]
+    Parse(String, String),
+    #[error("invalid address {0:?}")]
+    InvalidAddress(String),
+    #[error("no {0} configured")]
+    MissingRole(String),
+    #[error("more than one {0} configured")]
+    DuplicateRole(String),
+    #[error("pool needs either an address or a factory and two tokens")]
+    InvalidPool,
+    #[error("no gc accounts configured")]
+    NoGcAccounts,
+    #[error("invalid model parameters: {0}")]
+    InvalidModel(String),
+    #[error("invalid retry parameters: {0}")]
+    InvalidRetry(String),
+    #[error("no rpc endpoints configured")]
+    NoUpstreams,
+    #[error("invalid rpc endpoint {0:?}")]
+    InvalidUpstream(String),
+    #[error("invalid health check parameters: {0}")]
+    InvalidHealth(String),
+    #[error("invalid quorum parameters: {0}")]
+    InvalidQuorum(String),
+    #[error("invalid snapshot parameters: {0}")]
+    InvalidSnapshot(String),
+    #[error("invalid indexer parameters: {0}")]
+    InvalidIndexer(String),
+    #[error("no networks configured")]
+    NoNetworks,
+    #[error("invalid network name {0:?}")]
+    InvalidNetworkName(String),
+    #[error("network {0:?} configured more than once")]
+    DuplicateNetwork(String),
+    #[error("default network {0:?} is not configured")]
+    UnknownDefaultNetwork(String),
+    #[error("network {0}: {1}")]
+    Network(String, #[source] Box<ConfigError>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenRole {
+    // GTON, the token the peg is computed for
+    Gton,
+    // native wrapped token GTON is paired with, WFTM on Fantom
+    Base,
+    // USD stable coin
+    Usd,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PoolRole {
+    // GTON/base pool
+    Base,
+    // GTON/usd pool
+    Usd,
+    // base/usd pool, prices the base token in USD
+    Quote,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TokenConfig {
+    pub address: String,
+    pub role: Option<TokenRole>,
+    // when name, symbol and decimals are all set the token is not read from chain
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub decimals: Option<u8>,
+}
+
+// a pool is declared either by its address or by the factory and token pair
+// it is resolved from through `getPair`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PoolConfig {
+    pub address: Option<String>,
+    pub factory: Option<String>,
+    pub tokens: Option<[String; 2]>,
+    pub role: Option<PoolRole>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default)]
+pub struct ModelConfig {
+    pub gc_floor: f64,
+    pub gc_bias: f64,
+    pub gc_max_p: f64,
+    pub gc_max_l: f64,
+}
+
+impl Default for ModelConfig {
+    fn default() -> Self {
+        ModelConfig {
+            gc_floor: 2.05,
+            gc_bias: 1.7,
+            gc_max_p: 600.0,
+            gc_max_l: 550.0,
+        }
+    }
+}
+
+// applied to every read from the node, see retry.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    pub max_attempts: usize,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    // each delay is randomized by up to this fraction in either direction
+    pub jitter: f64,
+    // overall time budget of one read, attempts and delays included
+    pub deadline_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 5,
+            initial_delay_ms: 70,
+            max_delay_ms: 2000,
+            multiplier: 2.0,
+            jitter: 0.2,
+            deadline_ms: 15000,
+        }
+    }
+}
+
+// a node endpoint, lower `priority` is preferred while the endpoint is healthy
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpstreamConfig {
+    pub url: String,
+    #[serde(default)]
+    pub priority: u32,
+}
+
+impl UpstreamConfig {
+    pub fn new(url: &str) -> UpstreamConfig {
+        UpstreamConfig {
+            url: url.to_string(),
+            priority: 0,
+        }
+    }
+}
+
+// thresholds an upstream must stay within to receive requests, see upstream.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    pub interval_ms: u64,
+    // a single request to one upstream, the next one is tried when it runs out
+    pub request_timeout_ms: u64,
+    // blocks behind the most advanced upstream
+    pub max_block_lag: u64,
+    // share of failed requests among the last `window` ones
+    pub max_error_rate: f64,
+    pub window: usize,
+    pub max_latency_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        HealthConfig {
+            interval_ms: 5000,
+            request_timeout_ms: 5000,
+            max_block_lag: 5,
+            max_error_rate: 0.5,
+            window: 20,
+            max_latency_ms: 2000,
+        }
+    }
+}
+
+// the metric routes answer `latest` reads from a snapshot refreshed in the
+// background on every new block, see snapshot.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnapshotConfig {
+    pub enabled: bool,
+    // refresh at least this often when no new block is announced
+    pub interval_ms: u64,
+    // older snapshots are not served, the metric is computed on demand instead
+    pub max_age_ms: u64,
+    // snapshots kept for the history route, one every history_interval_ms, the
+    // last history_size are loaded back from the database on startup
+    pub history_interval_ms: u64,
+    pub history_size: usize,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        SnapshotConfig {
+            enabled: true,
+            interval_ms: 5000,
+            max_age_ms: 30000,
+            history_interval_ms: 60000,
+            history_size: 10080,
+        }
+    }
+}
+
+// thresholds `/readyz` holds every network to, see readiness.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadinessConfig {
+    // the latest block must have been mined within this
+    pub max_block_age_ms: u64,
+    // the node checks of one probe are abandoned after this
+    pub timeout_ms: u64,
+}
+
+impl Default for ReadinessConfig {
+    fn default() -> Self {
+        ReadinessConfig {
+            max_block_age_ms: 60000,
+            timeout_ms: 3000,
+        }
+    }
+}
+
+// Sync, Swap, Mint, Burn and Transfer events of the configured pools are
+// followed from `start_block` and kept in the database, see indexer.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexerConfig {
+    pub enabled: bool,
+    // the block the oldest pool was created at, earlier blocks hold no events
+    pub start_block: u64,
+    // blocks per eth_getLogs request, halved while the node rejects the range
+    pub chunk_size: u64,
+    // blocks below the head that are not indexed yet, in case they are reorged
+    pub confirmations: u64,
+    pub interval_ms: u64,
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        IndexerConfig {
+            enabled: false,
+            start_block: 0,
+            chunk_size: 2000,
+            confirmations: 5,
+            interval_ms: 5000,
+        }
+    }
+}
+
+// the GC POL and peg inputs are read from `size` upstreams at the same block
+// and only used when at least `min_agree` of them return identical results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuorumConfig {
+    pub size: usize,
+    pub min_agree: usize,
+}
+
+// one Pathway deployment, served under `/rpc/{name}/...`
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    pub name: String,
+    // shorthand for a single upstream with priority 0
+    pub rpc: Option<String>,
+    #[serde(default)]
+    pub upstreams: Vec<UpstreamConfig>,
+    // defaults to the canonical Multicall3 deployment
+    pub multicall: Option<String>,
+    // UniswapV2Router02 used by the quote routes when none is passed
+    pub router: Option<String>,
+    pub tokens: Vec<TokenConfig>,
+    pub pools: Vec<PoolConfig>,
+    pub gc_accounts: Vec<String>,
+    #[serde(default)]
+    pub model: ModelConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    // quorum reads are off unless configured
+    pub quorum: Option<QuorumConfig>,
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+    #[serde(default)]
+    pub indexer: IndexerConfig,
+    #[serde(default)]
+    pub readiness: ReadinessConfig,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Config {
+    // network the unprefixed `/rpc/...` routes are served from, the first one if unset
+    pub default_network: Option<String>,
+    // SQLite database of every network, `./data/pw.db` if unset, see store.rs
+    pub database: Option<String>,
+    pub networks: Vec<NetworkConfig>,
+}
+
+// first path segments of the unprefixed `/rpc/...` routes, a network named after
+// one of them would take that route over. read from the OpenAPI document, which
+// openapi.rs tests against the registered routes
+static RESERVED_NETWORK_NAMES: LazyLock<BTreeSet<String>> = LazyLock::new(|| {
+    ApiDoc::openapi()
+        .paths
+        .paths
+        .keys()
+        .filter_map(|path| path.strip_prefix("/rpc/"))
+        .filter_map(|path| path.split('/').next())
+        .map(String::from)
+        .collect()
+});
+
+const UPSTREAM_SCHEMES: &[&str] = &["http://", "https://", "ws://", "wss://", "ipc://"];
+
+fn validate_address(address: &str) -> Result<(), ConfigError> {
+    let hex_address = address.strip_prefix("0x").unwrap_or(address);
+    match hex::decode(hex_address) {
+        Ok(v) if v.len() == Address::len_bytes() => Ok(()),
+        _ => Err(ConfigError::InvalidAddress(address.to_string())),
+    }
+}
+
+impl Config {
+    // `.toml` files are parsed as TOML, anything else as JSON
+    pub fn from_file(path: &str) -> Result<Config, ConfigError> {
+        let contents = fs::read_to_string(Path::new(path))
+            .map_err(|e| ConfigError::Io(path.to_string(), e))?;
+
+        let config: Config = if path.ends_with(".toml") {
+            toml::from_str(&contents)
+                .map_err(|e| ConfigError::Parse(path.to_string(), e.to_string()))?
+        } else {
+            serde_json::from_str(&contents)
+                .map_err(|e| ConfigError::Parse(path.to_string(), e.to_string()))?
+        };
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.networks.is_empty() {
+            return Err(ConfigError::NoNetworks);
+        }
+
+        for (i, network) in self.networks.iter().enumerate() {
+            let name = network.name.as_str();
+            if name.is_empty()
+                || RESERVED_NETWORK_NAMES.contains(name)
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(ConfigError::InvalidNetworkName(network.name.clone()));
+            }
+            if self.networks[..i].iter().any(|n| n.name == network.name) {
+                return Err(ConfigError::DuplicateNetwork(network.name.clone()));
+            }
+
+            network
+                .validate()
+                .map_err(|e| ConfigError::Network(network.name.clone(), Box::new(e)))?;
+        }
+
+        if let Some(default) = &self.default_network {
+            if !self.networks.iter().any(|n| &n.name == default) {
+                return Err(ConfigError::UnknownDefaultNetwork(default.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn default_network(&self) -> &NetworkConfig {
+        match &self.default_network {
+            Some(name) => self.networks.iter().find(|n| &n.name == name).unwrap(),
+            None => &self.networks[0],
+        }
+    }
+
+    pub fn database(&self) -> String {
+        self.database
+            .clone()
+            .unwrap_or_else(|| String::from("./data/pw.db"))
+    }
+
+    pub fn default_network_mut(&mut self) -> &mut NetworkConfig {
+        let name = self.default_network().name.clone();
+        self.networks.iter_mut().find(|n| n.name == name).unwrap()
+    }
+}
+
+impl NetworkConfig {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let upstreams = self.upstreams();
+        if upstreams.is_empty() {
+            return Err(ConfigError::NoUpstreams);
+        }
+        for upstream in &upstreams {
+            // a bare absolute path is an IPC socket
+            if !(UPSTREAM_SCHEMES.iter().any(|s| upstream.url.starts_with(s))
+                || upstream.url.starts_with('/'))
+            {
+                return Err(ConfigError::InvalidUpstream(upstream.url.clone()));
+            }
+        }
+
+        for address in self.multicall.iter().chain(self.router.iter()) {
+            validate_address(address)?;
+        }
+        for token in &self.tokens {
+            validate_address(&token.address)?;
+        }
+        for pool in &self.pools {
+            match (&pool.address, &pool.factory, &pool.tokens) {
+                (Some(address), None, None) => validate_address(address)?,
+                (None, Some(factory), Some(tokens)) => {
+                    validate_address(factory)?;
+                    for token in tokens {
+                        validate_address(token)?;
+                    }
+                }
+                _ => return Err(ConfigError::InvalidPool),
+            }
+        }
+        for acc in &self.gc_accounts {
+            validate_address(acc)?;
+        }
+
+        for role in [TokenRole::Gton, TokenRole::Base, TokenRole::Usd] {
+            match self.tokens.iter().filter(|t| t.role == Some(role)).count() {
+                0 => return Err(ConfigError::MissingRole(format!("{:?} token", role))),
+                1 => {}
+                _ => return Err(ConfigError::DuplicateRole(format!("{:?} token", role))),
+            }
+        }
+        for role in [PoolRole::Base, PoolRole::Usd, PoolRole::Quote] {
+            match self.pools.iter().filter(|p| p.role == Some(role)).count() {
+                0 => return Err(ConfigError::MissingRole(format!("{:?} pool", role))),
+                1 => {}
+                _ => return Err(ConfigError::DuplicateRole(format!("{:?} pool", role))),
+            }
+        }
+
+        if self.gc_accounts.is_empty() {
+            return Err(ConfigError::NoGcAccounts);
+        }
+
+        let model = &self.model;
+        if model.gc_max_l == 0.0 {
+            return Err(ConfigError::InvalidModel(String::from(
+                "gc_max_l must not be zero",
+            )));
+        }
+        if [
+            model.gc_floor,
+            model.gc_bias,
+            model.gc_max_p,
+            model.gc_max_l,
+        ]
+        .iter()
+        .any(|v| !v.is_finite())
+        {
+            return Err(ConfigError::InvalidModel(String::from(
+                "parameters must be finite numbers",
+            )));
+        }
+
+        let retry = &self.retry;
+        if retry.max_attempts == 0 {
+            return Err(ConfigError::InvalidRetry(String::from(
+                "max_attempts must be at least 1",
+            )));
+        }
+        if !(retry.multiplier >= 1.0 && retry.multiplier.is_finite()) {
+            return Err(ConfigError::InvalidRetry(String::from(
+                "multiplier must be a finite number of at least 1",
+            )));
+        }
+        if !(0.0..=1.0).contains(&retry.jitter) {
+            return Err(ConfigError::InvalidRetry(String::from(
+                "jitter must be between 0 and 1",
+            )));
+        }
+        if retry.deadline_ms == 0 {
+            return Err(ConfigError::InvalidRetry(String::from(
+                "deadline_ms must not be zero",
+            )));
+        }
+        if retry.max_delay_ms < retry.initial_delay_ms {
+            return Err(ConfigError::InvalidRetry(String::from(
+                "max_delay_ms must not be less than initial_delay_ms",
+            )));
+        }
+
+        let health = &self.health;
+        if health.interval_ms == 0 || health.request_timeout_ms == 0 || health.window == 0 {
+            return Err(ConfigError::InvalidHealth(String::from(
+                "interval_ms, request_timeout_ms and window must not be zero",
+            )));
+        }
+        if !(0.0..=1.0).contains(&health.max_error_rate) {
+            return Err(ConfigError::InvalidHealth(String::from(
+                "max_error_rate must be between 0 and 1",
+            )));
+        }
+
+        if self.snapshot.interval_ms == 0 {
+            return Err(ConfigError::InvalidSnapshot(String::from(
+                "interval_ms must not be zero",
+            )));
+        }
+
+        if self.indexer.chunk_size == 0 || self.indexer.interval_ms == 0 {
+            return Err(ConfigError::InvalidIndexer(String::from(
+                "chunk_size and interval_ms must not be zero",
+            )));
+        }
+
+        if let Some(quorum) = &self.quorum {
+            if quorum.size > upstreams.len() {
+                return Err(ConfigError::InvalidQuorum(format!(
+                    "size {} exceeds the {} configured upstreams",
+                    quorum.size,
+                    upstreams.len()
+                )));
+            }
+            // two disagreeing results can't both reach a majority
+            if quorum.min_agree > quorum.size || quorum.min_agree * 2 <= quorum.size {
+                return Err(ConfigError::InvalidQuorum(String::from(
+                    "min_agree must be a majority of size",
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // `rpc` followed by `upstreams`
+    pub fn upstreams(&self) -> Vec<UpstreamConfig> {
+        self.rpc
+            .iter()
+            .map(|url| UpstreamConfig::new(url))
+            .chain(self.upstreams.iter().cloned())
+            .collect()
+    }
+
+    // roles are validated at startup, so lookups by role always succeed
+    pub fn token(&self, role: TokenRole) -> &str {
+        self.tokens
+            .iter()
+            .find(|t| t.role == Some(role))
+            .map(|t| t.address.as_str())
+            .unwrap()
+    }
+
+    // pools declared through a factory are resolved by Client::new
+    pub fn pool(&self, role: PoolRole) -> &str {
+        self.pools
+            .iter()
+            .find(|p| p.role == Some(role))
+            .and_then(|p| p.address.as_deref())
+            .unwrap()
+    }
+
+    pub fn gc_accounts(&self) -> Vec<&str> {
+        self.gc_accounts.iter().map(String::as_str).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_config() {
+        let config = Config::from_file("./config.toml").unwrap();
+        assert_eq!(config.default_network().name, "fantom");
+
+        let config = config.default_network();
+
+        assert_eq!(
+            config.pool(PoolRole::Base),
+            "0x25f5b3840d414a21c4fc46d21699e54d48f75fdd"
+        );
+        assert_eq!(
+            config.token(TokenRole::Usd),
+            "0x04068da6c83afcfa0e13ba15a6696662335d5b75"
+        );
+        assert_eq!(config.gc_accounts().len(), 3);
+        assert_eq!(config.model, ModelConfig::default());
+        assert_eq!(config.retry, RetryConfig::default());
+        assert_eq!(config.health, HealthConfig::default());
+        assert_eq!(config.snapshot, SnapshotConfig::default());
+        assert_eq!(config.indexer, IndexerConfig::default());
+        assert_eq!(config.readiness, ReadinessConfig::default());
+    }
+
+    #[test]
+    fn test_json_config() {
+        let path = std::env::temp_dir().join("pw-rs-test-config.json");
+        let mut config = Config::from_file("./config.toml").unwrap();
+        config.default_network_mut().model.gc_floor = 3.0;
+        fs::write(&path, serde_json::to_string(&config).unwrap()).unwrap();
+
+        let parsed = Config::from_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn test_validation() {
+        let config = Config::from_file("./config.toml")
+            .unwrap()
+            .default_network()
+            .clone();
+
+        let mut missing = config.clone();
+        missing.pools.retain(|p| p.role != Some(PoolRole::Quote));
+        assert!(matches!(
+            missing.validate(),
+            Err(ConfigError::MissingRole(_))
+        ));
+
+        let mut duplicate = config.clone();
+        duplicate.tokens.push(duplicate.tokens[0].clone());
+        assert!(matches!(
+            duplicate.validate(),
+            Err(ConfigError::DuplicateRole(_))
+        ));
+
+        let mut bad_address = config.clone();
+        bad_address.gc_accounts.push(String::from("0x1234"));
+        assert!(matches!(
+            bad_address.validate(),
+            Err(ConfigError::InvalidAddress(_))
+        ));
+
+        let mut factory_pool = config.clone();
+        factory_pool.pools[0] = PoolConfig {
+            address: None,
+            factory: Some(String::from("0x152eE697f2E276fA89E96742e9bB9aB1F2E61bE3")),
+            tokens: Some([
+                config.token(TokenRole::Gton).to_string(),
+                config.token(TokenRole::Base).to_string(),
+            ]),
+            role: Some(PoolRole::Base),
+        };
+        assert!(factory_pool.validate().is_ok());
+
+        let mut ambiguous_pool = factory_pool.clone();
+        ambiguous_pool.pools[0].address = Some(config.pool(PoolRole::Base).to_string());
+        assert!(matches!(
+            ambiguous_pool.validate(),
+            Err(ConfigError::InvalidPool)
+        ));
+
+        let mut no_accounts = config.clone();
+        no_accounts.gc_accounts.clear();
+        assert!(matches!(
+            no_accounts.validate(),
+            Err(ConfigError::NoGcAccounts)
+        ));
+
+        let mut bad_model = config.clone();
+        bad_model.model.gc_max_l = 0.0;
+        assert!(matches!(
+            bad_model.validate(),
+            Err(ConfigError::InvalidModel(_))
+        ));
+
+        let mut bad_retry = config.clone();
+        bad_retry.retry.jitter = 1.5;
+        assert!(matches!(
+            bad_retry.validate(),
+            Err(ConfigError::InvalidRetry(_))
+        ));
+
+        let mut no_deadline = config.clone();
+        no_deadline.retry.deadline_ms = 0;
+        assert!(matches!(
+            no_deadline.validate(),
+            Err(ConfigError::InvalidRetry(_))
+        ));
+
+        let mut short_max_delay = config.clone();
+        short_max_delay.retry.max_delay_ms = short_max_delay.retry.initial_delay_ms - 1;
+        assert!(matches!(
+            short_max_delay.validate(),
+            Err(ConfigError::InvalidRetry(_))
+        ));
+
+        let mut bad_snapshot = config.clone();
+        bad_snapshot.snapshot.interval_ms = 0;
+        assert!(matches!(
+            bad_snapshot.validate(),
+            Err(ConfigError::InvalidSnapshot(_))
+        ));
+
+        let mut bad_indexer = config.clone();
+        bad_indexer.indexer.chunk_size = 0;
+        assert!(matches!(
+            bad_indexer.validate(),
+            Err(ConfigError::InvalidIndexer(_))
+        ));
+
+        let mut bad_health = config.clone();
+        bad_health.health.max_error_rate = -0.1;
+        assert!(matches!(
+            bad_health.validate(),
+            Err(ConfigError::InvalidHealth(_))
+        ));
+    }
+
+    #[test]
+    fn test_upstreams() {
+        let mut config = Config::from_file("./config.toml")
+            .unwrap()
+            .default_network()
+            .clone();
+        config.upstreams.push(UpstreamConfig {
+            url: String::from("https://rpc.ftm.tools"),
+            priority: 2,
+        });
+        assert!(config.validate().is_ok());
+
+        let upstreams = config.upstreams();
+        assert_eq!(upstreams.len(), 3);
+        assert_eq!(upstreams[0].url, config.rpc.clone().unwrap());
+        assert_eq!(upstreams[0].priority, 0);
+
+        let mut no_rpc = config.clone();
+        no_rpc.rpc = None;
+        assert!(no_rpc.validate().is_ok());
+        no_rpc.upstreams.clear();
+        assert!(matches!(no_rpc.validate(), Err(ConfigError::NoUpstreams)));
+
+        let mut quorum = config.clone();
+        quorum.quorum = Some(QuorumConfig {
+            size: 3,
+            min_agree: 2,
+        });
+        assert!(quorum.validate().is_ok());
+
+        let mut too_large = quorum.clone();
+        too_large.quorum = Some(QuorumConfig {
+            size: 4,
+            min_agree: 3,
+        });
+        assert!(matches!(
+            too_large.validate(),
+            Err(ConfigError::InvalidQuorum(_))
+        ));
+
+        let mut no_majority = quorum;
+        no_majority.quorum = Some(QuorumConfig {
+            size: 2,
+            min_agree: 1,
+        });
+        assert!(matches!(
+            no_majority.validate(),
+            Err(ConfigError::InvalidQuorum(_))
+        ));
+
+        let mut socket = config.clone();
+        socket.upstreams[0].url = String::from("wss://wsapi.fantom.network");
+        socket
+            .upstreams
+            .push(UpstreamConfig::new("/var/run/opera.ipc"));
+        assert!(socket.validate().is_ok());
+
+        let mut bad_url = config;
+        bad_url.upstreams[0].url = String::from("rpc.ftm.tools");
+        assert!(matches!(
+            bad_url.validate(),
+            Err(ConfigError::InvalidUpstream(_))
+        ));
+    }
+
+    #[test]
+    fn test_networks() {
+        let config = Config::from_file("./config.toml").unwrap();
+
+        let mut other = config.default_network().clone();
+        other.name = String::from("ethereum");
+
+        let mut multi = config.clone();
+        multi.networks.push(other.clone());
+        multi.default_network = Some(String::from("ethereum"));
+        assert!(multi.validate().is_ok());
+        assert_eq!(multi.default_network().name, "ethereum");
+
+        let mut unknown_default = multi.clone();
+        unknown_default.default_network = Some(String::from("polygon"));
+        assert!(matches!(
+            unknown_default.validate(),
+            Err(ConfigError::UnknownDefaultNetwork(_))
+        ));
+
+        let mut duplicate = multi.clone();
+        duplicate.networks.push(other);
+        assert!(matches!(
+            duplicate.validate(),
+            Err(ConfigError::DuplicateNetwork(_))
+        ));
+
+        for name in ["tokens", "quote", "base-price", "pool"] {
+            let mut reserved = config.clone();
+            reserved.networks[0].name = String::from(name);
+            assert!(
+                matches!(reserved.validate(), Err(ConfigError::InvalidNetworkName(_))),
+                "{}",
+                name
+            );
+        }
+
+        let mut invalid = config;
+        invalid.networks[0].gc_accounts.clear();
+        assert!(matches!(
+            invalid.validate(),
+            Err(ConfigError::Network(_, _))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+use std::io;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::exporter;
+
+#[derive(Debug, Error)]
+pub enum PwError {
+    #[error("invalid address {0:?}")]
+    InvalidAddress(String),
+    #[error("invalid amount {0:?}")]
+    InvalidAmount(String),
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("token {0} is not part of pool {1}")]
+    TokenNotInPool(String, String),
+    #[error("no pair for {0} and {1}")]
+    PairNotFound(String, String),
+    #[error("block {0} not found")]
+    BlockNotFound(String),
+    #[error("no quote for path {0}: {1}")]
+    NoQuote(String, String),
+    #[error("division by zero computing {0}")]
+    DivisionByZero(&'static str),
+    #[error("{0} is not a finite number")]
+    NotFinite(&'static str),
+    #[error("pool {0} is not indexed")]
+    NotIndexed(String),
+    #[error("no candles for pool {0}, only the GTON pools have them")]
+    NoCandles(String),
+    #[error("only {0} upstreams agree, {1} required")]
+    NoQuorum(usize, usize),
+    #[error("node did not answer within {0:?}")]
+    Timeout(Duration),
+    #[error("contract call failed: {0}")]
+    Contract(web3::contract::Error),
+    #[error("node request failed: {0}")]
+    Node(#[from] web3::Error),
+    #[error("failed to read {0}: {1}")]
+    Io(String, #[source] io::Error),
+    #[error("database {0} failed: {1}")]
+    Store(String, #[source] rusqlite::Error),
+}
+
+pub type Result<T> = std::result::Result<T, PwError>;
+
+// whether the node answered that the call reverted, which it does again for
+// the same call on every node. code 3 is the standard one, some nodes answer
+// -32603 or -32000 with the reason in the message instead
+pub fn is_revert(e: &web3::Error) -> bool {
+    match e {
+        web3::Error::Rpc(e) => {
+            e.code.code() == 3 || e.message.to_lowercase().contains("execution reverted")
+        }
+        _ => false,
+    }
+}
+
+// a contract call failing in the node is the node's failure unless the call
+// reverted, a revert or output that doesn't decode is the contract's, which is
+// whatever address the request named
+impl From<web3::contract::Error> for PwError {
+    fn from(e: web3::contract::Error) -> PwError {
+        match e {
+            web3::contract::Error::Api(e) if !is_revert(&e) => PwError::Node(e),
+            e => PwError::Contract(e),
+        }
+    }
+}
+
+impl PwError {
+    // stable identifier returned to API clients next to the message
+    pub fn code(&self) -> &'static str {
+        match self {
+            PwError::InvalidAddress(_) => "invalid_address",
+            PwError::InvalidAmount(_) => "invalid_amount",
+            PwError::InvalidPath(_) => "invalid_path",
+            PwError::InvalidRequest(_) => "invalid_request",
+            PwError::TokenNotInPool(_, _) => "token_not_in_pool",
+            PwError::PairNotFound(_, _) => "pair_not_found",
+            PwError::BlockNotFound(_) => "block_not_found",
+            PwError::NoQuote(_, _) => "no_quote",
+            PwError::DivisionByZero(_) => "division_by_zero",
+            PwError::NotFinite(_) => "not_finite",
+            PwError::NotIndexed(_) => "not_indexed",
+            PwError::NoCandles(_) => "no_candles",
+            PwError::NoQuorum(_, _) => "no_quorum",
+            PwError::Timeout(_) => "timeout",
+            PwError::Contract(_) => "contract_error",
+            PwError::Node(_) => "node_error",
+            PwError::Io(_, _) | PwError::Store(_, _) => "internal_error",
+        }
+    }
+
+    // background tasks have no caller to answer to, their failures are logged
+    // and counted under the task they happened in
+    pub fn report(&self, network: &str, task: &str, what: &str) {
+        eprintln!("network {:} {:}: {:}: {:}", network, task, what, self);
+        exporter::record_error(self.code(), task);
+    }
+}
+
+pub fn finite(value: f64, what: &'static str) -> Result<f64> {
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(PwError::NotFinite(what))
+    }
+}
+
+pub fn checked_div(a: f64, b: f64, what: &'static str) -> Result<f64> {
+    if b == 0.0 {
+        return Err(PwError::DivisionByZero(what));
+    }
+    finite(a / b, what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_div() {
+        assert_eq!(checked_div(3.0, 2.0, "x").unwrap(), 1.5);
+        assert!(matches!(
+            checked_div(1.0, 0.0, "x"),
+            Err(PwError::DivisionByZero("x"))
+        ));
+        assert!(matches!(
+            checked_div(f64::NAN, 1.0, "x"),
+            Err(PwError::NotFinite("x"))
+        ));
+        assert!(matches!(
+            checked_div(f64::MAX, 0.5, "x"),
+            Err(PwError::NotFinite("x"))
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+use serde::{Deserialize, Serialize};
+
+use utoipa::ToSchema;
+
+use web3::ethabi::{self, RawLog, Token};
+use web3::types::{Address, Log, H256, U256};
+
+use crate::error::{PwError, Result};
+
+// events of the UniswapV2Pair ABI that are indexed, Approval is not
+const EVENT_NAMES: &[&str] = &["Sync", "Swap", "Mint", "Burn", "Transfer"];
+
+// a pair event, fields are named as in the ABI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type")]
+pub enum PairEvent {
+    #[serde(rename_all = "camelCase")]
+    Sync {
+        #[schema(value_type = String)]
+        reserve0: U256,
+        #[schema(value_type = String)]
+        reserve1: U256,
+    },
+    #[serde(rename_all = "camelCase")]
+    Swap {
+        #[schema(value_type = String)]
+        sender: Address,
+        #[schema(value_type = String)]
+        amount0_in: U256,
+        #[schema(value_type = String)]
+        amount1_in: U256,
+        #[schema(value_type = String)]
+        amount0_out: U256,
+        #[schema(value_type = String)]
+        amount1_out: U256,
+        #[schema(value_type = String)]
+        to: Address,
+    },
+    #[serde(rename_all = "camelCase")]
+    Mint {
+        #[schema(value_type = String)]
+        sender: Address,
+        #[schema(value_type = String)]
+        amount0: U256,
+        #[schema(value_type = String)]
+        amount1: U256,
+    },
+    #[serde(rename_all = "camelCase")]
+    Burn {
+        #[schema(value_type = String)]
+        sender: Address,
+        #[schema(value_type = String)]
+        amount0: U256,
+        #[schema(value_type = String)]
+        amount1: U256,
+        #[schema(value_type = String)]
+        to: Address,
+    },
+    #[serde(rename_all = "camelCase")]
+    Transfer {
+        #[schema(value_type = String)]
+        from: Address,
+        #[schema(value_type = String)]
+        to: Address,
+        #[schema(value_type = String)]
+        value: U256,
+    },
+}
+
+impl PairEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PairEvent::Sync { .. } => "Sync",
+            PairEvent::Swap { .. } => "Swap",
+            PairEvent::Mint { .. } => "Mint",
+            PairEvent::Burn { .. } => "Burn",
+            PairEvent::Transfer { .. } => "Transfer",
+        }
+    }
+}
+
+// a decoded event and the log it was read from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexedEvent {
+    #[schema(value_type = String)]
+    pub pool: Address,
+    pub block: u64,
+    pub timestamp: u64,
+    #[schema(value_type = String)]
+    pub transaction_hash: H256,
+    pub log_index: u64,
+    #[serde(flatten)]
+    pub event: PairEvent,
+}
+
+impl IndexedEvent {
+    // position of the event on chain, events are kept ordered by it
+    pub fn key(&self) -> (u64, u64) {
+        (self.block, self.log_index)
+    }
+}
+
+pub struct EventDecoder {
+    events: Vec<ethabi::Event>,
+}
+
+fn decode_error(event: &str, message: &str) -> PwError {
+    web3::Error::Decoder(format!("{} event: {}", event, message)).into()
+}
+
+impl EventDecoder {
+    pub fn new(pair_abi: &[u8]) -> Result<EventDecoder> {
+        let contract = ethabi::Contract::load(pair_abi).map_err(web3::contract::Error::from)?;
+        let events = EVENT_NAMES
+            .iter()
+            .map(|name| contract.event(name).cloned())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(web3::contract::Error::from)?;
+        Ok(EventDecoder { events })
+    }
+
+    // topic0 of every indexed event, for the eth_getLogs filter
+    pub fn topics(&self) -> Vec<H256> {
+        self.events.iter().map(|event| event.signature()).collect()
+    }
+
+    // None for logs of events that are not indexed
+    pub fn decode(&self, log: &Log) -> Result<Option<PairEvent>> {
+        let event = match log
+            .topics
+            .first()
+            .and_then(|topic| self.events.iter().find(|e| e.signature() == *topic))
+        {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+
+        let name = event.name.as_str();
+        let parsed = event
+            .parse_log(RawLog {
+                topics: log.topics.clone(),
+                data: log.data.0.clone(),
+            })
+            .map_err(|e| decode_error(name, &e.to_string()))?;
+        let param = |param: &str| -> Result<Token> {
+            parsed
+                .params
+                .iter()
+                .find(|p| p.name == param)
+                .map(|p| p.value.clone())
+                .ok_or_else(|| decode_error(name, &format!("missing {}", param)))
+        };
+        let address = |p: &str| -> Result<Address> {
+            param(p)?
+                .into_address()
+                .ok_or_else(|| decode_error(name, &format!("{} is not an address", p)))
+        };
+        let uint = |p: &str| -> Result<U256> {
+            param(p)?
+                .into_uint()
+                .ok_or_else(|| decode_error(name, &format!("{} is not a uint", p)))
+        };
+
+        let event = match name {
+            "Sync" => PairEvent::Sync {
+                reserve0: uint("reserve0")?,
+                reserve1: uint("reserve1")?,
+            },
+            "Swap" => PairEvent::Swap {
+                sender: address("sender")?,
+                amount0_in: uint("amount0In")?,
+                amount1_in: uint("amount1In")?,
+                amount0_out: uint("amount0Out")?,
+                amount1_out: uint("amount1Out")?,
+                to: address("to")?,
+            },
+            "Mint" => PairEvent::Mint {
+                sender: address("sender")?,
+                amount0: uint("amount0")?,
+                amount1: uint("amount1")?,
+            },
+            "Burn" => PairEvent::Burn {
+                sender: address("sender")?,
+                amount0: uint("amount0")?,
+                amount1: uint("amount1")?,
+                to: address("to")?,
+            },
+            _ => PairEvent::Transfer {
+                from: address("from")?,
+                to: address("to")?,
+                value: uint("value")?,
+            },
+        };
+        Ok(Some(event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use web3::types::Bytes;
+
+    fn decoder() -> EventDecoder {
+        EventDecoder::new(&std::fs::read("./abi/UniswapV2Pair.json").unwrap()).unwrap()
+    }
+
+    fn log(topics: Vec<H256>, data: Vec<Token>) -> Log {
+        Log {
+            address: Address::repeat_byte(0xaa),
+            topics,
+            data: Bytes(ethabi::encode(&data)),
+            block_hash: None,
+            block_number: Some(100.into()),
+            transaction_hash: Some(H256::repeat_byte(0x01)),
+            transaction_index: None,
+            log_index: Some(3.into()),
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    fn topic(address: Address) -> H256 {
+        H256::from(address)
+    }
+
+    #[test]
+    fn test_decode() {
+        let decoder = decoder();
+        let topics = decoder.topics();
+        assert_eq!(topics.len(), EVENT_NAMES.len());
+
+        let swap = log(
+            vec![
+                topics[1],
+                topic(Address::repeat_byte(0x11)),
+                topic(Address::repeat_byte(0x22)),
+            ],
+            vec![
+                Token::Uint(1000.into()),
+                Token::Uint(0.into()),
+                Token::Uint(0.into()),
+                Token::Uint(2500.into()),
+            ],
+        );
+        assert_eq!(
+            decoder.decode(&swap).unwrap(),
+            Some(PairEvent::Swap {
+                sender: Address::repeat_byte(0x11),
+                amount0_in: 1000.into(),
+                amount1_in: 0.into(),
+                amount0_out: 0.into(),
+                amount1_out: 2500.into(),
+                to: Address::repeat_byte(0x22),
+            })
+        );
+
+        let sync = log(
+            vec![topics[0]],
+            vec![Token::Uint(7.into()), Token::Uint(9.into())],
+        );
+        let event = decoder.decode(&sync).unwrap().unwrap();
+        assert_eq!(event.name(), "Sync");
+
+        let approval = log(vec![H256::repeat_byte(0xff)], vec![]);
+        assert_eq!(decoder.decode(&approval).unwrap(), None);
+
+        let truncated = log(vec![topics[1]], vec![Token::Uint(1.into())]);
+        assert!(matches!(
+            decoder.decode(&truncated),
+            Err(PwError::Node(web3::Error::Decoder(_)))
+        ));
+    }
+
+    #[test]
+    fn test_serialize() {
+        let event = IndexedEvent {
+            pool: Address::repeat_byte(0xaa),
+            block: 100,
+            timestamp: 1_650_000_000,
+            transaction_hash: H256::repeat_byte(0x01),
+            log_index: 3,
+            event: PairEvent::Mint {
+                sender: Address::repeat_byte(0x11),
+                amount0: 1.into(),
+                amount1: 2.into(),
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "Mint");
+        assert_eq!(json["logIndex"], 3);
+        assert_eq!(json["amount1"], "0x2");
+
+        let parsed: IndexedEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, event);
+    }
+}
@@ -0,0 +1,298 @@
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use prometheus::{
+    register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
+    Encoder, GaugeVec, HistogramVec, IntCounter, IntCounterVec, TextEncoder,
+};
+
+use crate::snapshot::Snapshots;
+
+// counters and histograms live in the default registry so the upstreams, the
+// retries and the HTTP middleware can record without holding a handle
+static UPSTREAM_REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "pw_upstream_requests_total",
+        "Requests sent to a node endpoint by outcome",
+        &["upstream", "outcome"]
+    )
+    .unwrap()
+});
+
+static UPSTREAM_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "pw_upstream_request_duration_seconds",
+        "Time a node endpoint took to answer",
+        &["upstream"]
+    )
+    .unwrap()
+});
+
+static RETRIES: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "pw_node_retries_total",
+        "Node reads sent again after a transient error"
+    )
+    .unwrap()
+});
+
+static ERRORS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "pw_errors_total",
+        "Errors by error code and where they happened",
+        &["code", "source"]
+    )
+    .unwrap()
+});
+
+static QUORUM_DISAGREEMENTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "pw_quorum_disagreements_total",
+        "Quorum reads a node endpoint was outvoted in",
+        &["network", "upstream"]
+    )
+    .unwrap()
+});
+
+static HTTP_LATENCY: LazyLock<HistogramVec> = LazyLock::new(|| {
+    register_histogram_vec!(
+        "pw_http_request_duration_seconds",
+        "Time a route took to answer",
+        &["route", "status"]
+    )
+    .unwrap()
+});
+
+// gauges are reset and set again from the latest snapshots on every scrape,
+// the lock is held until they are gathered so concurrent scrapes don't see
+// each other's half set gauges
+static GAUGES: LazyLock<Mutex<Gauges>> = LazyLock::new(|| Mutex::new(Gauges::register()));
+
+struct Gauges {
+    base_price: GaugeVec,
+    gton_price: GaugeVec,
+    pool_liquidity: GaugeVec,
+    pool_lp_supply: GaugeVec,
+    gc_pool_lps: GaugeVec,
+    gc_pol: GaugeVec,
+    gc_current_peg: GaugeVec,
+    snapshot_block: GaugeVec,
+    snapshot_age: GaugeVec,
+}
+
+impl Gauges {
+    fn register() -> Gauges {
+        let gauge = |name: &str, help: &str, labels: &[&str]| {
+            register_gauge_vec!(name, help, labels).unwrap()
+        };
+        Gauges {
+            base_price: gauge("pw_base_price", "Price of the base token", &["network"]),
+            gton_price: gauge(
+                "pw_gton_price",
+                "Price of GTON in the quote token",
+                &["network", "quote"],
+            ),
+            pool_liquidity: gauge(
+                "pw_pool_liquidity",
+                "Liquidity of a GTON pool",
+                &["network", "pool"],
+            ),
+            pool_lp_supply: gauge(
+                "pw_pool_lp_supply",
+                "LP tokens of a GTON pool",
+                &["network", "pool"],
+            ),
+            gc_pool_lps: gauge(
+                "pw_gc_pool_lps",
+                "LP tokens of a GTON pool held by the GC accounts",
+                &["network", "pool"],
+            ),
+            gc_pol: gauge(
+                "pw_gc_pol",
+                "Protocol owned liquidity of the GC accounts",
+                &["network"],
+            ),
+            gc_current_peg: gauge(
+                "pw_gc_current_peg",
+                "Current PW peg in the quote token",
+                &["network", "quote"],
+            ),
+            snapshot_block: gauge(
+                "pw_snapshot_block",
+                "Block the metrics were computed at",
+                &["network"],
+            ),
+            snapshot_age: gauge(
+                "pw_snapshot_age_seconds",
+                "Age of the snapshot the metrics were read from",
+                &["network"],
+            ),
+        }
+    }
+
+    fn reset(&self) {
+        for gauge in [
+            &self.base_price,
+            &self.gton_price,
+            &self.pool_liquidity,
+            &self.pool_lp_supply,
+            &self.gc_pool_lps,
+            &self.gc_pol,
+            &self.gc_current_peg,
+            &self.snapshot_block,
+            &self.snapshot_age,
+        ] {
+            gauge.reset();
+        }
+    }
+}
+
+pub fn record_upstream_request(upstream: &str, ok: bool, elapsed: Duration) {
+    let outcome = if ok { "success" } else { "failure" };
+    UPSTREAM_REQUESTS
+        .with_label_values(&[upstream, outcome])
+        .inc();
+    UPSTREAM_LATENCY
+        .with_label_values(&[upstream])
+        .observe(elapsed.as_secs_f64());
+}
+
+pub fn record_retry() {
+    RETRIES.inc();
+}
+
+// `source` is `http` or `jsonrpc` for failed requests and the name of the
+// background task otherwise
+pub fn record_error(code: &str, source: &str) {
+    ERRORS.with_label_values(&[code, source]).inc();
+}
+
+pub fn record_quorum_disagreement(network: &str, upstream: &str) {
+    QUORUM_DISAGREEMENTS
+        .with_label_values(&[network, upstream])
+        .inc();
+}
+
+// `route` is the matched route pattern, not the requested path
+pub fn record_http_request(route: &str, status: u16, elapsed: Duration) {
+    HTTP_LATENCY
+        .with_label_values(&[route, &status.to_string()])
+        .observe(elapsed.as_secs_f64());
+}
+
+// serves `/metrics`, the value gauges of a network are left out while it has
+// no snapshot younger than max_age_ms
+pub struct Exporter {
+    networks: Vec<(String, Arc<Snapshots>)>,
+}
+
+impl Exporter {
+    pub fn new(networks: Vec<(String, Arc<Snapshots>)>) -> Exporter {
+        Exporter { networks }
+    }
+
+    fn update(&self, gauges: &Gauges) {
+        gauges.reset();
+        for (network, snapshots) in &self.networks {
+            let snapshot = match snapshots.latest() {
+                Some(snapshot) => snapshot,
+                None => continue,
+            };
+            let m = &snapshot.metrics;
+            let set = |gauge: &GaugeVec, label: Option<&str>, value: f64| {
+                let mut labels = vec![network.as_str()];
+                labels.extend(label);
+                gauge.with_label_values(&labels).set(value);
+            };
+
+            set(&gauges.base_price, None, m.base_price);
+            set(&gauges.gton_price, Some("usd"), m.base_to_usdc_price);
+            set(&gauges.gton_price, Some("base"), m.base_to_quote_price);
+            set(&gauges.pool_liquidity, Some("base"), m.base_liquidity);
+            set(&gauges.pool_liquidity, Some("usd"), m.usd_liquidity);
+            set(&gauges.pool_lp_supply, Some("base"), m.base_pool_lps);
+            set(&gauges.pool_lp_supply, Some("usd"), m.usd_pool_lps);
+            set(&gauges.gc_pool_lps, Some("base"), m.owned_base_pool_lps);
+            set(&gauges.gc_pool_lps, Some("usd"), m.owned_usd_pool_lps);
+            set(&gauges.gc_pol, None, m.gc_pol);
+            set(&gauges.gc_current_peg, Some("usd"), m.gc_current_peg_usd);
+            set(&gauges.gc_current_peg, Some("base"), m.gc_current_peg_base);
+            set(&gauges.snapshot_block, None, snapshot.block as f64);
+            set(&gauges.snapshot_age, None, snapshot.age().as_secs_f64());
+        }
+    }
+
+    // every metric of the process in the Prometheus text format
+    pub fn render(&self) -> String {
+        let gauges = GAUGES.lock().unwrap();
+        self.update(&gauges);
+        let families = prometheus::gather();
+        drop(gauges);
+
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&families, &mut buffer).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    use crate::config::SnapshotConfig;
+    use crate::snapshot::{Metrics, Snapshot};
+    use crate::trace::Trace;
+
+    #[test]
+    fn test_render() {
+        let snapshots = Arc::new(Snapshots::new(&SnapshotConfig::default()));
+        snapshots.store(Snapshot {
+            block: 100,
+            timestamp: 6000,
+            taken_at: Instant::now(),
+            metrics: Metrics {
+                base_price: 0.25,
+                gc_current_peg_usd: 2.5,
+                ..Metrics::default()
+            },
+            trace: Trace::default(),
+        });
+        let exporter = Exporter::new(vec![
+            (String::from("fantom"), snapshots),
+            (
+                String::from("empty"),
+                Arc::new(Snapshots::new(&SnapshotConfig::default())),
+            ),
+        ]);
+        record_error("timeout", "http");
+        record_quorum_disagreement("fantom", "https://rpc.ftm.tools");
+        record_http_request("/rpc/base-price", 200, Duration::from_millis(5));
+
+        let text = exporter.render();
+        assert!(text.contains("pw_base_price{network=\"fantom\"} 0.25"));
+        assert!(text.contains("pw_gc_current_peg{network=\"fantom\",quote=\"usd\"} 2.5"));
+        assert!(text.contains("pw_snapshot_block{network=\"fantom\"} 100"));
+        assert!(!text.contains("network=\"empty\""));
+        assert!(text.contains("pw_errors_total{code=\"timeout\",source=\"http\"}"));
+        assert!(text.contains(
+            "pw_quorum_disagreements_total{network=\"fantom\",upstream=\"https://rpc.ftm.tools\"} 1"
+        ));
+        assert!(text.contains(
+            "pw_http_request_duration_seconds_count{route=\"/rpc/base-price\",status=\"200\"} 1"
+        ));
+
+        // concurrent scrapes each see every gauge
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        let text = exporter.render();
+                        assert!(text.contains("pw_base_price{network=\"fantom\"} 0.25"));
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,265 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::{StreamExt, TryStreamExt};
+
+use serde::{Deserialize, Serialize};
+
+use utoipa::ToSchema;
+
+use web3::types::BlockId;
+
+use crate::client::Client;
+use crate::error::{PwError, Result};
+use crate::snapshot::{block_number, compute_metric, metric, MetricFn, Snapshot, Snapshots};
+
+pub const MAX_HISTORY_POINTS: usize = 1000;
+
+// points missing from the stored snapshots that are recomputed at once
+const RECOMPUTE_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct HistoryPoint {
+    pub block: u64,
+    pub timestamp: u64,
+    pub value: f64,
+}
+
+// timestamps from `from` to `to`, `interval` seconds apart
+fn grid(from: u64, to: u64, interval: u64) -> Result<Vec<u64>> {
+    if interval == 0 || from > to {
+        return Err(PwError::InvalidRequest(String::from(
+            "interval must not be zero and from must not be after to",
+        )));
+    }
+    let points = (to - from) / interval + 1;
+    if points > MAX_HISTORY_POINTS as u64 {
+        return Err(PwError::InvalidRequest(format!(
+            "{} points requested, at most {} are served",
+            points, MAX_HISTORY_POINTS
+        )));
+    }
+    Ok((0..points).map(|i| from + i * interval).collect())
+}
+
+// the newest stored snapshot taken at most `tolerance` seconds before `timestamp`
+fn stored_point(
+    history: &[Arc<Snapshot>],
+    timestamp: u64,
+    tolerance: u64,
+    get: MetricFn,
+) -> Option<HistoryPoint> {
+    let end = history.partition_point(|s| s.timestamp <= timestamp);
+    history[..end]
+        .last()
+        .filter(|s| timestamp - s.timestamp < tolerance)
+        .map(|s| HistoryPoint {
+            block: s.block,
+            timestamp: s.timestamp,
+            value: get(&s.metrics),
+        })
+}
+
+// last block mined at or before `timestamp` between the blocks `lo` and `hi`,
+// given as (number, timestamp) with lo.1 <= timestamp < hi.1, interpolating
+// on block times and bisecting every other step so uneven ones still converge
+async fn search_block<F, Fut>(
+    mut lo: (u64, u64),
+    mut hi: (u64, u64),
+    timestamp: u64,
+    timestamp_of: F,
+) -> Result<(u64, u64)>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<u64>>,
+{
+    let mut bisect = false;
+    while hi.0 - lo.0 > 1 {
+        let guess = if bisect {
+            lo.0 + (hi.0 - lo.0) / 2
+        } else {
+            let elapsed = (timestamp - lo.1) as u128;
+            let span = (hi.1 - lo.1).max(1) as u128;
+            lo.0 + (elapsed * (hi.0 - lo.0) as u128 / span) as u64
+        }
+        .clamp(lo.0 + 1, hi.0 - 1);
+        bisect = !bisect;
+
+        let guess_timestamp = timestamp_of(guess).await?;
+        if guess_timestamp <= timestamp {
+            lo = (guess, guess_timestamp);
+        } else {
+            hi = (guess, guess_timestamp);
+        }
+    }
+    Ok(lo)
+}
+
+// the first and the latest block as (number, timestamp), resolved once per
+// history and shared by the searches of its points
+pub struct ChainBounds {
+    genesis: (u64, u64),
+    latest: (u64, u64),
+}
+
+impl ChainBounds {
+    pub async fn resolve(client: &Client) -> Result<ChainBounds> {
+        let latest = block_number(client.resolve_block(None, None).await?)
+            .ok_or_else(|| PwError::BlockNotFound(String::from("latest")))?;
+        let (latest_timestamp, genesis_timestamp) = futures::try_join!(
+            client.get_block_timestamp(latest),
+            client.get_block_timestamp(0)
+        )?;
+        Ok(ChainBounds {
+            genesis: (0, genesis_timestamp),
+            latest: (latest, latest_timestamp),
+        })
+    }
+}
+
+// the chain state at `timestamp`, as (block number, block timestamp)
+pub async fn block_at(client: &Client, bounds: &ChainBounds, timestamp: u64) -> Result<(u64, u64)> {
+    if timestamp >= bounds.latest.1 {
+        return Ok(bounds.latest);
+    }
+    if timestamp < bounds.genesis.1 {
+        return Err(PwError::BlockNotFound(format!(
+            "at timestamp {}",
+            timestamp
+        )));
+    }
+
+    search_block(bounds.genesis, bounds.latest, timestamp, |number| {
+        client.get_block_timestamp(number)
+    })
+    .await
+}
+
+// `metric` every `interval` seconds from `from` to `to`, taken from the stored
+// snapshots where they cover the point and recomputed at the block of the
+// point otherwise, which for old blocks needs an archive node
+pub async fn metric_history(
+    client: &Client,
+    snapshots: &Snapshots,
+    name: &str,
+    from: u64,
+    to: u64,
+    interval: u64,
+) -> Result<Vec<HistoryPoint>> {
+    let get = metric(name)
+        .ok_or_else(|| PwError::InvalidRequest(format!("unknown metric {:?}", name)))?;
+    let points = grid(from, to, interval)?;
+
+    let history = snapshots.history(from.saturating_sub(interval), to);
+    let tolerance = interval.min(snapshots.history_interval().max(1));
+    let stored: Vec<_> = points
+        .iter()
+        .map(|&timestamp| stored_point(&history, timestamp, tolerance, get))
+        .collect();
+    // the chain is only asked for its bounds when a point has to be recomputed
+    let bounds = if stored.iter().any(Option::is_none) {
+        Some(ChainBounds::resolve(client).await?)
+    } else {
+        None
+    };
+    let bounds = bounds.as_ref();
+
+    futures::stream::iter(points.into_iter().zip(stored))
+        .map(|(timestamp, stored)| async move {
+            if let Some(point) = stored {
+                return Ok(point);
+            }
+            let bounds = bounds.expect("bounds are resolved for missing points");
+            let (block, timestamp) = block_at(client, bounds, timestamp).await?;
+            let value = compute_metric(client, name, BlockId::Number(block.into())).await?;
+            Ok(HistoryPoint {
+                block,
+                timestamp,
+                value,
+            })
+        })
+        .buffered(RECOMPUTE_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use crate::snapshot::Metrics;
+    use crate::trace::Trace;
+
+    #[test]
+    fn test_grid() {
+        assert_eq!(grid(100, 400, 100).unwrap(), vec![100, 200, 300, 400]);
+        assert_eq!(grid(100, 450, 100).unwrap(), vec![100, 200, 300, 400]);
+        assert_eq!(grid(100, 100, 100).unwrap(), vec![100]);
+        assert!(grid(100, 400, 0).is_err());
+        assert!(grid(400, 100, 100).is_err());
+        assert!(grid(0, 1_000_000, 1).is_err());
+    }
+
+    #[test]
+    fn test_stored_point() {
+        let history: Vec<Arc<Snapshot>> = [100, 160, 220]
+            .iter()
+            .map(|&timestamp| {
+                Arc::new(Snapshot {
+                    block: timestamp / 2,
+                    timestamp,
+                    taken_at: Instant::now(),
+                    metrics: Metrics {
+                        gc_pol: timestamp as f64,
+                        ..Metrics::default()
+                    },
+                    trace: Trace::default(),
+                })
+            })
+            .collect();
+        let get = metric("gc-pol").unwrap();
+
+        let point = stored_point(&history, 200, 60, get).unwrap();
+        assert_eq!(
+            (point.block, point.timestamp, point.value),
+            (80, 160, 160.0)
+        );
+        assert_eq!(stored_point(&history, 220, 60, get).unwrap().block, 110);
+        assert!(stored_point(&history, 50, 60, get).is_none());
+        assert!(stored_point(&history, 300, 60, get).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_search_block() {
+        // one block a second, then a ten minute stall and two seconds a block
+        let timestamp_of = |number: u64| {
+            if number <= 5000 {
+                1000 + number
+            } else {
+                1000 + 5000 + 600 + (number - 5001) * 2
+            }
+        };
+        let requests = AtomicUsize::new(0);
+        let search = |timestamp| {
+            search_block(
+                (0, timestamp_of(0)),
+                (20000, timestamp_of(20000)),
+                timestamp,
+                |number| {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    async move { Ok(timestamp_of(number)) }
+                },
+            )
+        };
+
+        assert_eq!(search(1000).await.unwrap(), (0, 1000));
+        assert_eq!(search(3500).await.unwrap(), (2500, 3500));
+        // inside the stall the block before it is the state at that time
+        assert_eq!(search(6300).await.unwrap(), (5000, 6000));
+        assert_eq!(search(6603).await.unwrap(), (5002, 6602));
+        assert!(requests.load(Ordering::SeqCst) < 4 * 2 * 16);
+    }
+}
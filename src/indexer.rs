@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use futures::{StreamExt, TryStreamExt};
+
+use tokio::sync::watch;
+
+use web3::types::{Address, BlockNumber, FilterBuilder};
+
+use crate::client::Client;
+use crate::config::IndexerConfig;
+use crate::error::{PwError, Result};
+use crate::events::{EventDecoder, IndexedEvent};
+use crate::snapshot::block_number;
+use crate::store::Store;
+
+// block timestamps fetched concurrently for one chunk of logs
+const TIMESTAMP_CONCURRENCY: usize = 8;
+
+// pair events of the configured pools, written to the database along with
+// the indexed block and served from there
+pub struct Indexer {
+    config: IndexerConfig,
+    network: String,
+    store: Arc<Store>,
+    pools: Vec<Address>,
+    decoder: EventDecoder,
+    // every event up to this block is indexed
+    cursor: RwLock<Option<u64>>,
+    // the cursor, announced whenever it moves
+    indexed: watch::Sender<Option<u64>>,
+    // blocks per eth_getLogs, halved from config.chunk_size while the node
+    // refuses or times out on a range and kept for the later runs
+    chunk_size: AtomicU64,
+}
+
+// the node refused the range or gave up on it, a smaller one may still pass
+fn is_range_error(e: &PwError) -> bool {
+    match e {
+        // nodes cap the block range and the number of logs of a request
+        PwError::Node(web3::Error::Rpc(_)) | PwError::Timeout(_) => true,
+        PwError::Node(e @ web3::Error::Transport(_)) => e.to_string().contains("timed out"),
+        _ => false,
+    }
+}
+
+impl Indexer {
+    pub async fn new(
+        config: &IndexerConfig,
+        network: &str,
+        client: &Client,
+        store: Arc<Store>,
+    ) -> Result<Indexer> {
+        let indexer = Indexer {
+            config: config.clone(),
+            network: network.to_string(),
+            store,
+            pools: client.pools()?,
+            decoder: EventDecoder::new(client.pair_abi())?,
+            cursor: RwLock::new(None),
+            indexed: watch::channel(None).0,
+            chunk_size: AtomicU64::new(config.chunk_size),
+        };
+        if config.enabled {
+            indexer.load().await?;
+        }
+        Ok(indexer)
+    }
+
+    pub fn is_indexed(&self, pool: Address) -> bool {
+        self.config.enabled && self.pools.contains(&pool)
+    }
+
+    pub fn cursor(&self) -> Option<u64> {
+        *self.cursor.read().unwrap()
+    }
+
+    pub fn watch(&self) -> watch::Receiver<Option<u64>> {
+        self.indexed.subscribe()
+    }
+
+    // events of `pool` within `blocks`, oldest first, optionally of a single type
+    pub async fn events(
+        &self,
+        pool: Address,
+        blocks: RangeInclusive<u64>,
+        name: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<IndexedEvent>> {
+        if blocks.is_empty() {
+            return Ok(vec![]);
+        }
+        self.store
+            .events(&self.network, pool, blocks, name, offset, limit)
+            .await
+    }
+
+    // picks up where earlier runs stopped, the events and the cursor are
+    // committed together so they always match
+    async fn load(&self) -> Result<()> {
+        let cursor = self.store.event_cursor(&self.network).await?;
+        *self.cursor.write().unwrap() = cursor;
+        self.indexed.send_replace(cursor);
+        Ok(())
+    }
+
+    // events already stored, left over from a run that stopped before moving
+    // the cursor, are ignored by the database
+    async fn commit(&self, events: Vec<IndexedEvent>, cursor: u64) -> Result<()> {
+        self.store
+            .insert_events(&self.network, &events, cursor)
+            .await?;
+        *self.cursor.write().unwrap() = Some(cursor);
+        self.indexed.send_replace(Some(cursor));
+        Ok(())
+    }
+
+    async fn fetch_events(&self, client: &Client, from: u64, to: u64) -> Result<Vec<IndexedEvent>> {
+        let filter = FilterBuilder::default()
+            .address(self.pools.clone())
+            .topics(Some(self.decoder.topics()), None, None, None)
+            .from_block(BlockNumber::Number(from.into()))
+            .to_block(BlockNumber::Number(to.into()))
+            .build();
+        let logs = client.get_logs(filter).await?;
+
+        let mut blocks: Vec<u64> = logs
+            .iter()
+            .filter_map(|log| log.block_number)
+            .map(|number| number.as_u64())
+            .collect();
+        blocks.sort_unstable();
+        blocks.dedup();
+        let timestamps: HashMap<u64, u64> = futures::stream::iter(blocks)
+            .map(|number| async move {
+                Ok::<_, PwError>((number, client.get_block_timestamp(number).await?))
+            })
+            .buffered(TIMESTAMP_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+        let mut events = vec![];
+        for log in logs.iter().filter(|log| !log.is_removed()) {
+            let (block, transaction_hash, log_index) =
+                match (log.block_number, log.transaction_hash, log.log_index) {
+                    (Some(block), Some(hash), Some(index)) => {
+                        (block.as_u64(), hash, index.as_u64())
+                    }
+                    _ => continue,
+                };
+            // a log that doesn't decode would stop the indexer on every retry
+            let event = match self.decoder.decode(log) {
+                Ok(Some(event)) => event,
+                Ok(None) => continue,
+                Err(e) => {
+                    let what = format!("skipped log {:} of block {:}", log_index, block);
+                    e.report(&self.network, "indexer", &what);
+                    continue;
+                }
+            };
+            events.push(IndexedEvent {
+                pool: log.address,
+                block,
+                timestamp: timestamps.get(&block).copied().unwrap_or_default(),
+                transaction_hash,
+                log_index,
+                event,
+            });
+        }
+        Ok(events)
+    }
+
+    // indexes from the cursor up to `confirmations` blocks below the head,
+    // the cursor is moved after every chunk so a restart resumes from there
+    async fn catch_up(&self, client: &Client) -> Result<()> {
+        let head = block_number(client.resolve_block(None, None).await?)
+            .ok_or_else(|| PwError::BlockNotFound(String::from("latest")))?;
+        let target = head.saturating_sub(self.config.confirmations);
+
+        let mut from = self
+            .cursor()
+            .map_or(self.config.start_block, |cursor| cursor + 1)
+            .max(self.config.start_block);
+        while from <= target {
+            let chunk_size = self.chunk_size.load(Ordering::Relaxed);
+            let to = target.min(from + chunk_size - 1);
+            match self.fetch_events(client, from, to).await {
+                Ok(events) => {
+                    self.commit(events, to).await?;
+                    from = to + 1;
+                }
+                Err(e) if is_range_error(&e) && to > from => {
+                    let span = to - from + 1;
+                    self.chunk_size.store(span / 2, Ordering::Relaxed);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    // follows new blocks announced by the upstreams and at least every
+    // interval_ms, a failed run is retried on the next one
+    pub fn spawn_follower(self: Arc<Self>, client: Arc<Client>, network: String) {
+        if !self.config.enabled {
+            return;
+        }
+        let interval = Duration::from_millis(self.config.interval_ms);
+
+        tokio::spawn(async move {
+            let mut heads = client.watch_heads();
+            loop {
+                if let Err(e) = self.catch_up(&client).await {
+                    e.report(&network, "indexer", "indexing failed");
+                }
+                if let Ok(Err(_)) = tokio::time::timeout(interval, heads.changed()).await {
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use web3::types::H256;
+
+    use crate::events::PairEvent;
+
+    fn indexer(name: &str) -> Indexer {
+        let path = std::env::temp_dir().join(format!("pw-rs-test-indexer-{}.db", name));
+        let path = path.to_str().unwrap();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+        Indexer {
+            config: IndexerConfig {
+                enabled: true,
+                ..IndexerConfig::default()
+            },
+            network: String::from("fantom"),
+            store: Arc::new(Store::open(path).unwrap()),
+            pools: vec![Address::repeat_byte(0xaa)],
+            decoder: EventDecoder::new(&std::fs::read("./abi/UniswapV2Pair.json").unwrap())
+                .unwrap(),
+            cursor: RwLock::new(None),
+            indexed: watch::channel(None).0,
+            chunk_size: AtomicU64::new(IndexerConfig::default().chunk_size),
+        }
+    }
+
+    fn sync(pool: u8, block: u64, log_index: u64) -> IndexedEvent {
+        IndexedEvent {
+            pool: Address::repeat_byte(pool),
+            block,
+            timestamp: block * 2,
+            transaction_hash: H256::repeat_byte(0x01),
+            log_index,
+            event: PairEvent::Sync {
+                reserve0: block.into(),
+                reserve1: log_index.into(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recovery() {
+        let indexer = indexer("recovery");
+        indexer
+            .commit(vec![sync(0xaa, 12, 0), sync(0xaa, 10, 4)], 15)
+            .await
+            .unwrap();
+        // a chunk indexed again after a crash only adds what is new
+        indexer
+            .commit(vec![sync(0xaa, 12, 0), sync(0xaa, 16, 1)], 20)
+            .await
+            .unwrap();
+        assert_eq!(indexer.cursor(), Some(20));
+        assert_eq!(*indexer.watch().borrow(), Some(20));
+
+        // a restart reads back what was committed
+        let reloaded = Indexer {
+            cursor: RwLock::new(None),
+            ..indexer
+        };
+        reloaded.load().await.unwrap();
+        assert_eq!(reloaded.cursor(), Some(20));
+        let blocks: Vec<u64> = reloaded
+            .events(Address::repeat_byte(0xaa), 0..=u64::MAX, None, 0, 100)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.block)
+            .collect();
+        assert_eq!(blocks, vec![10, 12, 16]);
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let indexer = indexer("events");
+        indexer
+            .commit(
+                vec![
+                    sync(0xaa, 10, 0),
+                    sync(0xbb, 10, 1),
+                    sync(0xaa, 11, 0),
+                    sync(0xaa, 12, 0),
+                ],
+                12,
+            )
+            .await
+            .unwrap();
+        let pool = Address::repeat_byte(0xaa);
+
+        assert!(indexer.is_indexed(pool));
+        assert!(!indexer.is_indexed(Address::repeat_byte(0xbb)));
+        let events =
+            |blocks, name, offset, limit| indexer.events(pool, blocks, name, offset, limit);
+        assert_eq!(events(0..=100, None, 0, 100).await.unwrap().len(), 3);
+        assert_eq!(events(11..=11, None, 0, 100).await.unwrap()[0].block, 11);
+        assert_eq!(events(0..=100, None, 1, 1).await.unwrap()[0].block, 11);
+        assert_eq!(
+            events(0..=100, Some("sync"), 0, 100).await.unwrap().len(),
+            3
+        );
+        assert!(events(0..=100, Some("Swap"), 0, 100)
+            .await
+            .unwrap()
+            .is_empty());
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = events(12..=10, None, 0, 100).await.unwrap();
+        assert!(reversed.is_empty());
+    }
+
+    #[test]
+    fn test_range_error() {
+        let rpc = serde_json::json!({ "code": -32005, "message": "limit exceeded" });
+        let rpc = PwError::Node(web3::Error::Rpc(serde_json::from_value(rpc).unwrap()));
+        assert!(is_range_error(&rpc));
+        assert!(is_range_error(&PwError::Timeout(Duration::from_secs(15))));
+        let timed_out = web3::error::TransportError::Message(String::from("request timed out"));
+        assert!(is_range_error(&PwError::Node(web3::Error::Transport(
+            timed_out
+        ))));
+        assert!(!is_range_error(&PwError::Node(web3::Error::Unreachable)));
+    }
+}
@@ -0,0 +1,424 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use utoipa::ToSchema;
+
+use crate::candles::Candles;
+use crate::client::Client;
+use crate::error::PwError;
+use crate::exporter;
+use crate::indexer::Indexer;
+use crate::rpc::{
+    candles_result, history_result, metric_result, pool_events_result, quote_result,
+    BlockQueryArgs, CandlesQueryArgs, FactoryPairsQueryArgs, HistoryQueryArgs, PWModelQueryArgs,
+    PoolEventsQueryArgs, PoolPriceQueryArgs, QuoteQueryArgs, TwapQueryArgs, MAX_PAIRS_LIMIT,
+};
+use crate::snapshot::{compute_metric, metric, Snapshots};
+use crate::store::Store;
+
+// calls of one batch, larger batches are rejected as a whole
+pub const MAX_BATCH_SIZE: usize = 100;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+// failures of the call itself, the PwError code is in `data`
+pub const SERVER_ERROR: i64 = -32000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: &str) -> RpcError {
+        RpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+
+    // the PwError code of a failed call, or what was wrong with the call itself
+    fn metric_code(&self) -> &str {
+        let code = self.data.as_ref().and_then(|data| data.get("code"));
+        match (code.and_then(Value::as_str), self.code) {
+            (Some(code), _) => code,
+            (None, PARSE_ERROR) => "parse_error",
+            (None, METHOD_NOT_FOUND) => "method_not_found",
+            (None, INVALID_PARAMS) => "invalid_params",
+            (None, INTERNAL_ERROR) => "internal_error",
+            (None, _) => "invalid_request",
+        }
+    }
+}
+
+impl From<PwError> for RpcError {
+    fn from(e: PwError) -> RpcError {
+        let code = match e {
+            PwError::InvalidAddress(_)
+            | PwError::InvalidAmount(_)
+            | PwError::InvalidPath(_)
+            | PwError::InvalidRequest(_) => INVALID_PARAMS,
+            PwError::Io(_, _) | PwError::Store(_, _) => INTERNAL_ERROR,
+            _ => SERVER_ERROR,
+        };
+        RpcError {
+            code,
+            message: e.to_string(),
+            data: Some(json!({ "code": e.code() })),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+    pub id: Value,
+}
+
+impl RpcResponse {
+    pub fn result(id: Value, result: Value) -> RpcResponse {
+        RpcResponse {
+            jsonrpc: String::from("2.0"),
+            result: Some(result),
+            error: None,
+            id,
+        }
+    }
+
+    pub fn error(id: Value, error: RpcError) -> RpcResponse {
+        RpcResponse {
+            jsonrpc: String::from("2.0"),
+            result: None,
+            error: Some(error),
+            id,
+        }
+    }
+}
+
+// what the REST routes take from app data, for the network the call was posted to
+pub struct Context<'a> {
+    pub client: &'a Client,
+    pub snapshots: &'a Snapshots,
+    pub indexer: &'a Indexer,
+    pub candles: &'a Candles,
+    pub store: &'a Store,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddressParams {
+    address: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShareParams {
+    address: String,
+    account: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PoolParams {
+    pool: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetricParams {
+    metric: String,
+}
+
+// named params only, a missing `params` is the same as `{}`
+fn params<T: DeserializeOwned>(params: &Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        Value::Object(_) => params.clone(),
+        _ => return Err(RpcError::new(INVALID_PARAMS, "params must be an object")),
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, &e.to_string()))
+}
+
+fn to_value<T: Serialize>(result: T) -> Result<Value, RpcError> {
+    Ok(serde_json::to_value(result).unwrap())
+}
+
+// every REST route is a method named after its path, path segments become params:
+// `pool/reserves` takes `address`, `pool/share` takes `address` and `account`,
+// `candles` takes `pool` and `history` takes `metric`. only the `result` of the
+// route is returned, its `block`, `ageMs` and `meta` are dropped, so `meta`
+// has no effect here
+pub async fn dispatch(method: &str, raw: &Value, ctx: &Context<'_>) -> Result<Value, RpcError> {
+    let client = ctx.client;
+
+    if let Some(get) = metric(method) {
+        let block_args: BlockQueryArgs = params(raw)?;
+        let response = metric_result(&block_args, client, ctx.snapshots, get, |block| {
+            compute_metric(client, method, block)
+        })
+        .await?;
+        return to_value(response.result);
+    }
+
+    let block = || async {
+        let block_args: BlockQueryArgs = params(raw)?;
+        Ok::<_, RpcError>(
+            client
+                .resolve_block(block_args.block, block_args.tag)
+                .await?,
+        )
+    };
+    match method {
+        "pw-model-peg-with-pol-mln" => {
+            let args: PWModelQueryArgs = params(raw)?;
+            to_value(
+                client
+                    .get_pw_model_with_pol_mln(
+                        args.pol,
+                        args.gc_floor,
+                        args.gc_bias,
+                        args.gc_max_p,
+                        args.gc_max_l,
+                    )
+                    .await?,
+            )
+        }
+        "pool/reserves" => {
+            let args: AddressParams = params(raw)?;
+            to_value(
+                client
+                    .get_pool_reserves(&args.address, block().await?)
+                    .await?,
+            )
+        }
+        "pool/price" => {
+            let args: AddressParams = params(raw)?;
+            let price: PoolPriceQueryArgs = params(raw)?;
+            to_value(
+                client
+                    .get_pool_spot_price(&args.address, price.base.as_deref(), block().await?)
+                    .await?,
+            )
+        }
+        "pool/liquidity" => {
+            let args: AddressParams = params(raw)?;
+            to_value(
+                client
+                    .get_pool_liquidity(&args.address, block().await?)
+                    .await?,
+            )
+        }
+        "pool/lp-supply" => {
+            let args: AddressParams = params(raw)?;
+            to_value(
+                client
+                    .get_pool_lp_supply(&args.address, block().await?)
+                    .await?,
+            )
+        }
+        "pool/share" => {
+            let args: ShareParams = params(raw)?;
+            to_value(
+                client
+                    .get_pool_share(&args.address, &args.account, block().await?)
+                    .await?,
+            )
+        }
+        "pool/events" => {
+            let args: AddressParams = params(raw)?;
+            let events: PoolEventsQueryArgs = params(raw)?;
+            to_value(
+                pool_events_result(&args.address, &events, ctx.indexer)
+                    .await?
+                    .result,
+            )
+        }
+        "candles" => {
+            let args: PoolParams = params(raw)?;
+            let query: CandlesQueryArgs = params(raw)?;
+            to_value(candles_result(&args.pool, &query, ctx.candles, ctx.indexer)?.result)
+        }
+        "twap/base-price" | "twap/base-to-usdc-price" | "twap/base-to-quote-price" => {
+            let args: TwapQueryArgs = params(raw)?;
+            let block = block().await?;
+            let twap = match method {
+                "twap/base-price" => client.get_wftm_twap(args.window, block).await?,
+                "twap/base-to-usdc-price" => client.get_gton_usdc_twap(args.window, block).await?,
+                _ => client.get_gton_wftm_twap(args.window, block).await?,
+            };
+            to_value(twap)
+        }
+        "tokens" => to_value(client.get_assets()),
+        "token" => {
+            let args: AddressParams = params(raw)?;
+            to_value(client.get_asset(&args.address, block().await?).await?)
+        }
+        "factory/pairs" => {
+            let args: AddressParams = params(raw)?;
+            let page: FactoryPairsQueryArgs = params(raw)?;
+            to_value(
+                client
+                    .get_factory_pairs(
+                        &args.address,
+                        page.offset,
+                        page.limit.min(MAX_PAIRS_LIMIT),
+                        block().await?,
+                    )
+                    .await?,
+            )
+        }
+        "quote" | "quote-in" => {
+            let args: QuoteQueryArgs = params(raw)?;
+            let block_args: BlockQueryArgs = params(raw)?;
+            let response = quote_result(&args, method == "quote", &block_args, client).await?;
+            to_value(response.result)
+        }
+        "history" => {
+            let args: MetricParams = params(raw)?;
+            let query: HistoryQueryArgs = params(raw)?;
+            let response = history_result(&args.metric, &query, client, ctx.snapshots).await?;
+            to_value(response.result)
+        }
+        "admin/upstreams" => to_value(client.get_upstreams()),
+        "admin/model-history" => to_value(ctx.store.model_history(&client.config().name).await?),
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, "method not found")),
+    }
+}
+
+// every error answered, and those of notifications, is counted
+fn failed(id: Value, error: RpcError) -> RpcResponse {
+    exporter::record_error(error.metric_code(), "jsonrpc");
+    RpcResponse::error(id, error)
+}
+
+// a single call, None for notifications, which are run but not answered
+async fn call(request: &Value, ctx: &Context<'_>) -> Option<RpcResponse> {
+    let id = request.get("id").cloned();
+    let invalid = |id: Option<Value>| {
+        Some(failed(
+            id.unwrap_or(Value::Null),
+            RpcError::new(INVALID_REQUEST, "invalid request"),
+        ))
+    };
+
+    let object = match request.as_object() {
+        Some(object) => object,
+        None => return invalid(None),
+    };
+    let method = match (object.get("jsonrpc"), object.get("method")) {
+        (Some(Value::String(version)), Some(Value::String(method))) if version == "2.0" => method,
+        _ => return invalid(id),
+    };
+    if !matches!(
+        id,
+        None | Some(Value::Null) | Some(Value::String(_)) | Some(Value::Number(_))
+    ) {
+        return invalid(None);
+    }
+
+    let params = object.get("params").unwrap_or(&Value::Null);
+    let result = dispatch(method, params, ctx).await;
+    if let (Err(e), None) = (&result, &id) {
+        exporter::record_error(e.metric_code(), "jsonrpc");
+    }
+    let id = id?;
+    Some(match result {
+        Ok(result) => RpcResponse::result(id, result),
+        Err(e) => failed(id, e),
+    })
+}
+
+// a call or a batch of them, the calls of a batch run concurrently and are
+// answered in the order they were sent; None when nothing is to be answered
+pub async fn handle(body: &[u8], ctx: &Context<'_>) -> Option<Value> {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(_) => {
+            let error = failed(Value::Null, RpcError::new(PARSE_ERROR, "parse error"));
+            return Some(serde_json::to_value(error).unwrap());
+        }
+    };
+
+    match request {
+        Value::Array(calls) if calls.is_empty() || calls.len() > MAX_BATCH_SIZE => {
+            let message = if calls.is_empty() {
+                String::from("empty batch")
+            } else {
+                format!("batch of more than {} calls", MAX_BATCH_SIZE)
+            };
+            let error = failed(Value::Null, RpcError::new(INVALID_REQUEST, &message));
+            Some(serde_json::to_value(error).unwrap())
+        }
+        Value::Array(calls) => {
+            let responses: Vec<RpcResponse> =
+                futures::future::join_all(calls.iter().map(|c| call(c, ctx)))
+                    .await
+                    .into_iter()
+                    .flatten()
+                    .collect();
+            if responses.is_empty() {
+                return None;
+            }
+            Some(serde_json::to_value(responses).unwrap())
+        }
+        request => call(&request, ctx)
+            .await
+            .map(|response| serde_json::to_value(response).unwrap()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_params() {
+        let args: BlockQueryArgs = params(&json!({"block": 100, "other": 1})).unwrap();
+        assert_eq!(args.block, Some(100));
+        let args: BlockQueryArgs = params(&Value::Null).unwrap();
+        assert_eq!(args.block, None);
+
+        let positional = params::<BlockQueryArgs>(&json!([100])).unwrap_err();
+        assert_eq!(positional.code, INVALID_PARAMS);
+        let missing = params::<AddressParams>(&json!({})).unwrap_err();
+        assert_eq!(missing.code, INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let error = RpcError::from(PwError::InvalidAddress(String::from("0x1")));
+        assert_eq!(error.code, INVALID_PARAMS);
+        assert_eq!(error.data, Some(json!({"code": "invalid_address"})));
+
+        let error = RpcError::from(PwError::NotIndexed(String::from("0x1")));
+        assert_eq!(error.code, SERVER_ERROR);
+        assert_eq!(error.data, Some(json!({"code": "not_indexed"})));
+        assert_eq!(error.metric_code(), "not_indexed");
+        assert_eq!(
+            RpcError::new(METHOD_NOT_FOUND, "method not found").metric_code(),
+            "method_not_found"
+        );
+    }
+
+    #[test]
+    fn test_response() {
+        let response = RpcResponse::result(json!(1), json!(0.25));
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            json!({"jsonrpc": "2.0", "result": 0.25, "id": 1})
+        );
+        let response = RpcResponse::error(Value::Null, RpcError::new(PARSE_ERROR, "parse error"));
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            json!({"jsonrpc": "2.0", "error": {"code": -32700, "message": "parse error"}, "id": null})
+        );
+    }
+}
@@ -1,39 +1,202 @@
+use std::time::Instant;
+
+use actix_web::dev::Service;
 use actix_web::{web, App, HttpServer};
 
+use clap::Parser;
+
+mod assets;
+mod candles;
 mod client;
+mod config;
+mod error;
+mod events;
+mod exporter;
+mod history;
+mod indexer;
+mod jsonrpc;
+mod multicall;
+mod openapi;
+mod readiness;
+mod retry;
 mod rpc;
+mod snapshot;
+mod store;
+mod trace;
+mod transport;
+mod twap;
+mod upstream;
 
+use crate::candles::Candles;
 use crate::client::{Client, Props};
+use crate::config::Config;
+use crate::exporter::Exporter;
+use crate::indexer::Indexer;
+use crate::readiness::Probe;
 use crate::rpc::*;
+use crate::snapshot::Snapshots;
+use crate::store::Store;
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Node RPC endpoint of the default network, replaces its `rpc` and `upstreams` from the config
+    #[clap(long, env = "RPC")]
+    rpc: Option<String>,
+    /// Path to the network, token, pool and GC account config, `.toml` or `.json`
+    #[clap(long, env = "PW_CONFIG", default_value = "./config.toml")]
+    config: String,
+    /// Path to the SQLite database, replaces `database` from the config
+    #[clap(long, env = "PW_DATABASE")]
+    database: Option<String>,
+}
 
 #[tokio::main] // or #[tokio::main]
 async fn main() -> std::io::Result<()> {
-    let endpoint = match std::env::var("RPC") {
-        Ok(v) => v,
-        _ => String::from("https://rpcapi-tracing.fantom.network"),
-    };
+    let args = Args::parse();
+
+    let mut config = Config::from_file(&args.config)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    if let Some(rpc) = args.rpc {
+        let network = config.default_network_mut();
+        network.rpc = Some(rpc);
+        network.upstreams.clear();
+    }
+    if let Some(database) = args.database {
+        config.database = Some(database);
+    }
+
+    println!("config being used: {:}", args.config);
+
+    let database = config.database();
+    println!("database being used: {:}", database);
+    let store = web::Data::new(Store::open(&database).map_err(std::io::Error::other)?);
+
+    let mut clients = vec![];
+    for network in &config.networks {
+        let upstreams = network.upstreams();
+        for upstream in &upstreams {
+            println!(
+                "network {:} rpc being used: {:} (priority {:})",
+                network.name, upstream.url, upstream.priority
+            );
+        }
+
+        let client = Client::new(Props {
+            upstreams,
+            config: network.clone(),
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        let client = web::Data::new(client);
+        store
+            .record_model(&network.name, &network.model)
+            .await
+            .map_err(std::io::Error::other)?;
+
+        let snapshots = web::Data::new(Snapshots::new(&network.snapshot));
+        snapshots
+            .restore(&store, &network.name)
+            .await
+            .map_err(std::io::Error::other)?;
+        snapshots.clone().into_inner().spawn_refresher(
+            client.clone().into_inner(),
+            store.clone().into_inner(),
+            network.name.clone(),
+        );
+
+        let indexer = Indexer::new(
+            &network.indexer,
+            &network.name,
+            &client,
+            store.clone().into_inner(),
+        )
+        .await
+        .map_err(std::io::Error::other)?;
+        let indexer = web::Data::new(indexer);
+        indexer
+            .clone()
+            .into_inner()
+            .spawn_follower(client.clone().into_inner(), network.name.clone());
+
+        let candles = web::Data::new(Candles::new(&client).map_err(std::io::Error::other)?);
+        candles.clone().into_inner().spawn_updater(
+            client.clone().into_inner(),
+            indexer.clone().into_inner(),
+            network.name.clone(),
+        );
+
+        clients.push((network.name.clone(), client, snapshots, indexer, candles));
+    }
+    let default_network = config.default_network().name.clone();
+    let (_, default_client, default_snapshots, default_indexer, default_candles) = clients
+        .iter()
+        .find(|(name, _, _, _, _)| *name == default_network)
+        .cloned()
+        .unwrap();
 
-    println!("rpc being used: {:}", endpoint);
+    let exporter = web::Data::new(Exporter::new(
+        clients
+            .iter()
+            .map(|(name, _, snapshots, _, _)| (name.clone(), snapshots.clone().into_inner()))
+            .collect(),
+    ));
 
-    let app_data = Client::new(Props { node_rpc: endpoint }).await;
+    let probe = web::Data::new(Probe::new(
+        clients
+            .iter()
+            .map(|(name, client, snapshots, _, _)| {
+                (
+                    name.clone(),
+                    client.clone().into_inner(),
+                    snapshots.clone().into_inner(),
+                )
+            })
+            .collect(),
+        store.clone().into_inner(),
+    ));
 
     HttpServer::new(move || {
-        let app = App::new()
-            .app_data(web::Data::new(app_data.clone()))
-            .service(get_wftm_price)
-            .service(get_wftm_gton_gc_pool_lp)
-            .service(get_usdc_gton_gc_pool_lp)
-            .service(get_ftm_gton_liq)
-            .service(get_usdc_gton_liq)
-            .service(get_ftm_gton_lp)
-            .service(get_gc_pol)
-            .service(get_pw_model_with_pol_mln)
-            .service(get_gc_pw_current_peg_usd)
-            .service(get_gc_pw_current_peg_ftm)
-            .service(get_gton_usdc_price)
-            .service(get_gton_wftm_price);
-
-        app
+        let mut app = App::new()
+            .wrap_fn(|req, srv| {
+                let started = Instant::now();
+                let response = srv.call(req);
+                async move {
+                    let response = response.await?;
+                    // by route pattern, so paths carrying addresses don't add labels
+                    let route = response
+                        .request()
+                        .match_pattern()
+                        .unwrap_or_else(|| String::from("unmatched"));
+                    exporter::record_http_request(
+                        &route,
+                        response.status().as_u16(),
+                        started.elapsed(),
+                    );
+                    Ok(response)
+                }
+            })
+            .app_data(store.clone())
+            .app_data(exporter.clone())
+            .app_data(probe.clone())
+            .configure(root_routes);
+        for (name, client, snapshots, indexer, candles) in &clients {
+            app = app.service(
+                web::scope(&format!("/rpc/{}", name))
+                    .app_data(client.clone())
+                    .app_data(snapshots.clone())
+                    .app_data(indexer.clone())
+                    .app_data(candles.clone())
+                    .configure(routes),
+            );
+        }
+        app.service(
+            web::scope("/rpc")
+                .app_data(default_client.clone())
+                .app_data(default_snapshots.clone())
+                .app_data(default_indexer.clone())
+                .app_data(default_candles.clone())
+                .configure(routes),
+        )
     })
     .bind(("0.0.0.0", 8881))?
     .run()
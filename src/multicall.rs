@@ -0,0 +1,338 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use web3::{
+    contract::tokens::{Detokenize, Tokenize},
+    contract::{Contract, Error, Options},
+    ethabi::{Function, Token},
+    types::{Address, BlockId, BlockNumber, Bytes, CallRequest},
+    *,
+};
+
+use crate::trace;
+use crate::upstream::Upstreams;
+
+// Multicall3 is deployed at the same address on most EVM chains
+pub const MULTICALL3: &str = "0xcA11bde05977b3631167028862bE2a173976CA11";
+
+#[derive(Debug, Clone)]
+pub struct Call {
+    target: Address,
+    function: Function,
+    data: Vec<u8>,
+}
+
+impl Call {
+    pub fn new<T: Transport, P: Tokenize>(
+        contract: &Contract<T>,
+        function: &str,
+        params: P,
+    ) -> Call {
+        let function = contract.abi().function(function).unwrap().clone();
+        let data = function.encode_input(&params.into_tokens()).unwrap();
+
+        Call {
+            target: contract.address(),
+            function,
+            data,
+        }
+    }
+}
+
+// reads collected for one computation, executed as a single aggregate3
+#[derive(Debug, Clone, Default)]
+pub struct Batch {
+    calls: Vec<Call>,
+}
+
+impl Batch {
+    pub fn add(&mut self, call: Call) -> usize {
+        self.calls.push(call);
+        self.calls.len() - 1
+    }
+
+    pub async fn execute<T: Transport>(
+        &self,
+        multicall: &Multicall<T>,
+        block: BlockId,
+    ) -> std::result::Result<BatchResults, Error> {
+        let outputs = multicall.aggregate(&self.calls, block).await?;
+        trace::record_sources(self.calls.iter().map(|call| call.target));
+
+        Ok(BatchResults {
+            calls: self.calls.clone(),
+            outputs,
+        })
+    }
+}
+
+pub struct BatchResults {
+    calls: Vec<Call>,
+    outputs: Vec<Vec<u8>>,
+}
+
+impl BatchResults {
+    pub fn agrees_with(&self, other: &BatchResults) -> bool {
+        self.outputs == other.outputs
+    }
+
+    pub fn get<R: Detokenize>(&self, index: usize) -> std::result::Result<R, Error> {
+        let tokens = self.calls[index]
+            .function
+            .decode_output(&self.outputs[index])?;
+        R::from_tokens(tokens)
+    }
+}
+
+// how long the code read at the latest block is trusted for unpinned reads
+const LATEST_TTL: Duration = Duration::from_secs(300);
+
+// blocks Multicall3 was found deployed and missing at, reads at blocks before
+// the deployment fall back to one eth_call per read
+#[derive(Debug, Default)]
+struct Deployment {
+    // lowest block the contract has code at
+    deployed: Option<u64>,
+    // highest block it has none at
+    missing: Option<u64>,
+    // whether the latest block had code and when that was read, an unpinned
+    // read has no number to record a side of the deployment at
+    latest: Option<(bool, Instant)>,
+}
+
+impl Deployment {
+    // None until the code at a block on the same side of the deployment was read,
+    // an unpinned block is the latest one
+    fn known(&self, block: Option<u64>) -> Option<bool> {
+        match block {
+            Some(n) if self.deployed.is_some_and(|d| d <= n) => Some(true),
+            Some(n) if self.missing.is_some_and(|m| m >= n) => Some(false),
+            Some(_) => None,
+            None if self.deployed.is_some() => Some(true),
+            None => self
+                .latest
+                .filter(|(_, read_at)| read_at.elapsed() < LATEST_TTL)
+                .map(|(supported, _)| supported),
+        }
+    }
+
+    fn record(&mut self, block: Option<u64>, supported: bool) {
+        match block {
+            Some(block) if supported => {
+                self.deployed = Some(self.deployed.map_or(block, |d| d.min(block)))
+            }
+            Some(block) => self.missing = Some(self.missing.map_or(block, |m| m.max(block))),
+            None => self.latest = Some((supported, Instant::now())),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Multicall<T: Transport = Upstreams> {
+    web3: Web3<T>,
+    contract: Contract<T>,
+    deployment: Arc<Mutex<Deployment>>,
+}
+
+impl<T: Transport> Multicall<T> {
+    pub fn new(
+        web3: Web3<T>,
+        abi: &[u8],
+        address: Address,
+    ) -> std::result::Result<Multicall<T>, Error> {
+        let contract = Contract::from_json(web3.eth(), address, abi)?;
+
+        Ok(Multicall {
+            web3,
+            contract,
+            deployment: Arc::new(Mutex::new(Deployment::default())),
+        })
+    }
+
+    // the same contract read through another transport, e.g. a single upstream
+    pub fn with_transport<U: Transport>(&self, transport: U) -> Multicall<U> {
+        let web3 = Web3::new(transport);
+        let contract = Contract::new(
+            web3.eth(),
+            self.contract.address(),
+            self.contract.abi().clone(),
+        );
+
+        Multicall {
+            web3,
+            contract,
+            deployment: self.deployment.clone(),
+        }
+    }
+
+    // whether Multicall3 is deployed at `block`, the code is read once per side
+    // of the deployment and for unpinned reads once per LATEST_TTL
+    async fn is_supported(&self, block: BlockId) -> std::result::Result<bool, Error> {
+        let number = match block {
+            BlockId::Number(BlockNumber::Number(number)) => Some(number.as_u64()),
+            _ => None,
+        };
+        if let Some(supported) = self.deployment.lock().unwrap().known(number) {
+            return Ok(supported);
+        }
+
+        let code = self
+            .web3
+            .eth()
+            .code(self.contract.address(), number.map(|n| n.into()))
+            .await?;
+        let supported = !code.0.is_empty();
+        self.deployment.lock().unwrap().record(number, supported);
+        Ok(supported)
+    }
+
+    pub async fn aggregate(
+        &self,
+        calls: &[Call],
+        block: BlockId,
+    ) -> std::result::Result<Vec<Vec<u8>>, Error> {
+        if calls.is_empty() {
+            return Ok(vec![]);
+        }
+        if !self.is_supported(block).await? {
+            return self.call_each(calls, block).await;
+        }
+
+        let calls = calls
+            .iter()
+            .map(|call| {
+                Token::Tuple(vec![
+                    Token::Address(call.target),
+                    Token::Bool(false),
+                    Token::Bytes(call.data.clone()),
+                ])
+            })
+            .collect();
+        let result: Token = self
+            .contract
+            .query(
+                "aggregate3",
+                &[Token::Array(calls)][..],
+                None,
+                Options::default(),
+                block,
+            )
+            .await?;
+
+        // (success, returnData) per call, no call is allowed to fail so a
+        // failing one reverts the whole aggregate3 instead
+        result
+            .into_array()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| match r {
+                Token::Tuple(mut r) if r.len() == 2 => match r.remove(1) {
+                    Token::Bytes(data) => Ok(data),
+                    other => Err(Error::InvalidOutputType(format!(
+                        "Expected aggregate3 return data, got {:?}",
+                        other
+                    ))),
+                },
+                other => Err(Error::InvalidOutputType(format!(
+                    "Expected aggregate3 result, got {:?}",
+                    other
+                ))),
+            })
+            .collect()
+    }
+
+    async fn call_each(
+        &self,
+        calls: &[Call],
+        block: BlockId,
+    ) -> std::result::Result<Vec<Vec<u8>>, Error> {
+        let eth = self.web3.eth();
+        let results = futures::future::try_join_all(calls.iter().map(|call| {
+            eth.call(
+                CallRequest {
+                    to: Some(call.target),
+                    data: Some(Bytes(call.data.clone())),
+                    ..Default::default()
+                },
+                Some(block),
+            )
+        }))
+        .await?;
+
+        Ok(results.into_iter().map(|r| r.0).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use web3::types::U256;
+
+    fn pair_contract() -> Contract<transports::Http> {
+        let web3 = Web3::new(transports::Http::new("http://localhost:8545").unwrap());
+        let abi = std::fs::read("./abi/UniswapV2Pair.json").unwrap();
+        Contract::from_json(web3.eth(), Address::repeat_byte(1), abi.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_call_encoding() {
+        let call = Call::new(&pair_contract(), "balanceOf", Address::repeat_byte(2));
+
+        assert_eq!(call.target, Address::repeat_byte(1));
+        assert_eq!(&call.data[..4], &hex::decode("70a08231").unwrap()[..]);
+        assert_eq!(&call.data[16..], Address::repeat_byte(2).as_bytes());
+    }
+
+    #[test]
+    fn test_deployment() {
+        let mut deployment = Deployment::default();
+        assert_eq!(deployment.known(Some(100)), None);
+        assert_eq!(deployment.known(None), None);
+
+        deployment.record(Some(100), true);
+        deployment.record(Some(40), false);
+        assert_eq!(deployment.known(Some(150)), Some(true));
+        assert_eq!(deployment.known(Some(100)), Some(true));
+        assert_eq!(deployment.known(Some(30)), Some(false));
+        assert_eq!(deployment.known(Some(60)), None);
+        assert_eq!(deployment.known(None), Some(true));
+
+        deployment.record(Some(60), true);
+        assert_eq!(deployment.known(Some(60)), Some(true));
+
+        let mut missing = Deployment::default();
+        missing.record(None, false);
+        assert_eq!(missing.known(None), Some(false));
+        assert_eq!(missing.known(Some(100)), None);
+        missing.latest = Some((false, Instant::now() - LATEST_TTL));
+        assert_eq!(missing.known(None), None);
+    }
+
+    #[test]
+    fn test_batch_results_decoding() {
+        let contract = pair_contract();
+        let mut batch = Batch::default();
+        let supply = batch.add(Call::new(&contract, "totalSupply", ()));
+        let reserves = batch.add(Call::new(&contract, "getReserves", ()));
+
+        let results = BatchResults {
+            calls: batch.calls.clone(),
+            outputs: vec![
+                web3::ethabi::encode(&[Token::Uint(42.into())]),
+                web3::ethabi::encode(&[
+                    Token::Uint(1000.into()),
+                    Token::Uint(4000.into()),
+                    Token::Uint(1650000000.into()),
+                ]),
+            ],
+        };
+
+        assert_eq!(results.get::<U256>(supply).unwrap(), U256::from(42));
+        assert_eq!(
+            results.get::<(U256, U256, u32)>(reserves).unwrap(),
+            (U256::from(1000), U256::from(4000), 1650000000)
+        );
+        assert!(results.get::<Address>(reserves).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use utoipa::OpenApi;
+
+use crate::rpc;
+
+// routes of one network, the same document holds for `/rpc/{network}/...`
+#[derive(OpenApi)]
+#[openapi(paths(
+    rpc::get_wftm_price,
+    rpc::get_wftm_gton_gc_pool_lp,
+    rpc::get_usdc_gton_gc_pool_lp,
+    rpc::get_ftm_gton_liq,
+    rpc::get_usdc_gton_liq,
+    rpc::get_ftm_gton_lp,
+    rpc::get_usdc_gton_lp,
+    rpc::get_gc_pol,
+    rpc::get_pw_model_with_pol_mln,
+    rpc::get_gc_pw_current_peg_usd,
+    rpc::get_gc_pw_current_peg_ftm,
+    rpc::get_gton_usdc_price,
+    rpc::get_gton_wftm_price,
+    rpc::get_pool_reserves,
+    rpc::get_pool_spot_price,
+    rpc::get_pool_liquidity,
+    rpc::get_pool_lp_supply,
+    rpc::get_pool_share,
+    rpc::get_pool_events,
+    rpc::get_candles,
+    rpc::get_wftm_twap,
+    rpc::get_gton_usdc_twap,
+    rpc::get_gton_wftm_twap,
+    rpc::get_tokens,
+    rpc::get_token,
+    rpc::get_factory_pairs,
+    rpc::get_quote,
+    rpc::get_quote_in,
+    rpc::get_history,
+    rpc::get_upstreams,
+    rpc::get_model_history,
+    rpc::post_jsonrpc,
+))]
+struct NetworkApi;
+
+// served at `/openapi.json` with Swagger UI at `/docs/`
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Pathway API",
+        description = "Every `/rpc/...` route is also served per network under `/rpc/{network}/...`, \
+                       the unprefixed routes are aliases for the default network."
+    ),
+    paths(rpc::get_metrics, rpc::get_healthz, rpc::get_readyz),
+    nest((path = "/rpc", api = NetworkApi))
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use actix_web::http::StatusCode;
+    use actix_web::{test as actix_test, web, App};
+
+    fn documented() -> BTreeSet<(String, String)> {
+        let mut routes = BTreeSet::new();
+        for (path, item) in ApiDoc::openapi().paths.paths {
+            for (method, operation) in [
+                ("GET", &item.get),
+                ("POST", &item.post),
+                ("PUT", &item.put),
+                ("DELETE", &item.delete),
+            ] {
+                if operation.is_some() {
+                    routes.insert((method.to_string(), path.clone()));
+                }
+            }
+        }
+        routes
+    }
+
+    // `#[get("...")]` and `#[post("...")]` attributes of rpc.rs, `/rpc`
+    // prefixed unless registered by root_routes
+    fn registered() -> BTreeSet<(String, String)> {
+        let source = include_str!("rpc.rs");
+        let root = ["/metrics", "/healthz", "/readyz"];
+        let mut routes = BTreeSet::new();
+        for line in source.lines() {
+            for method in ["get", "post"] {
+                let prefix = format!("#[{}(\"", method);
+                if let Some(path) = line
+                    .trim()
+                    .strip_prefix(&prefix)
+                    .and_then(|rest| rest.strip_suffix("\")]"))
+                {
+                    // actix regex segments are documented without the regex
+                    let path = path.replace(":.*}", "}");
+                    let path = if root.contains(&path.as_str()) {
+                        path
+                    } else {
+                        format!("/rpc{}", path)
+                    };
+                    routes.insert((method.to_uppercase(), path));
+                }
+            }
+        }
+        routes
+    }
+
+    #[test]
+    fn test_spec_matches_handlers() {
+        assert_eq!(documented(), registered());
+        assert!(documented().len() > 30);
+    }
+
+    // every documented route reaches a handler, which fails without app data
+    // instead of answering 404
+    #[actix_web::test]
+    async fn test_spec_routes_are_served() {
+        let app = actix_test::init_service(
+            App::new()
+                .configure(rpc::root_routes)
+                .service(web::scope("/rpc").configure(rpc::routes)),
+        )
+        .await;
+
+        for (method, path) in documented() {
+            let uri = path
+                .replace("{address}", "0x25f5b3840d414a21c4fc46d21699e54d48f75fdd")
+                .replace("{account}", "0xB3D22267E7260ec6c3931d50D215ABa5Fd54506a")
+                .replace("{pool}", "0x25f5b3840d414a21c4fc46d21699e54d48f75fdd")
+                .replace("{metric}", "base-price");
+            let request = match method.as_str() {
+                "POST" => actix_test::TestRequest::post(),
+                _ => actix_test::TestRequest::get(),
+            }
+            .uri(&uri)
+            .to_request();
+            let response = actix_test::call_service(&app, request).await;
+            assert_ne!(
+                response.status(),
+                StatusCode::NOT_FOUND,
+                "{} {} is documented but not served",
+                method,
+                path
+            );
+        }
+
+        for uri in ["/openapi.json", "/docs/"] {
+            let response = actix_test::call_service(
+                &app,
+                actix_test::TestRequest::get().uri(uri).to_request(),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        }
+    }
+}
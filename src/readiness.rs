@@ -0,0 +1,216 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use utoipa::ToSchema;
+
+use crate::client::Client;
+use crate::error::{PwError, Result};
+use crate::snapshot::{block_number, Snapshots};
+use crate::store::Store;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    // why the check failed, or what it found
+    pub message: Option<String>,
+    // unix time the check has been passing or failing since, kept in the store
+    pub since: Option<u64>,
+}
+
+impl Check {
+    fn new(name: &str, result: std::result::Result<Option<String>, String>) -> Check {
+        let ok = result.is_ok();
+        Check {
+            name: name.to_string(),
+            ok,
+            message: result.unwrap_or_else(Some),
+            since: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkReadiness {
+    pub network: String,
+    pub ready: bool,
+    pub latest_block: Option<u64>,
+    pub block_age_ms: Option<u64>,
+    pub snapshot_age_ms: Option<u64>,
+    pub checks: Vec<Check>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Readiness {
+    pub ready: bool,
+    pub networks: Vec<NetworkReadiness>,
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+// block timestamps are in seconds, a block from the future counts as fresh
+fn block_age_ms(timestamp: u64, now_ms: u64) -> u64 {
+    now_ms.saturating_sub(timestamp * 1000)
+}
+
+// number and timestamp of the latest block, read from the node
+async fn latest_block(client: &Client) -> Result<(u64, u64)> {
+    let number = block_number(client.resolve_block(None, None).await?)
+        .ok_or_else(|| PwError::BlockNotFound(String::from("latest")))?;
+    Ok((number, client.get_block_timestamp(number).await?))
+}
+
+// probed by `/readyz`, a network is ready while its node answers, its latest
+// block is fresh, every ABI parses and its snapshot, when enabled, is not stale.
+// the outcome of every check is kept as alert state, so a check that starts
+// failing is reported with the time it did so even across restarts
+pub struct Probe {
+    networks: Vec<(String, Arc<Client>, Arc<Snapshots>)>,
+    store: Arc<Store>,
+}
+
+impl Probe {
+    pub fn new(networks: Vec<(String, Arc<Client>, Arc<Snapshots>)>, store: Arc<Store>) -> Probe {
+        Probe { networks, store }
+    }
+
+    // a store failure leaves `since` unset instead of failing the probe
+    async fn record_alerts(&self, network: &str, checks: &mut [Check]) {
+        let recorded = async {
+            for check in checks.iter() {
+                let state = if check.ok { "ok" } else { "failing" };
+                self.store.set_alert(network, &check.name, state).await?;
+            }
+            self.store.alerts(network).await
+        };
+        match recorded.await {
+            Ok(alerts) => {
+                for check in checks.iter_mut() {
+                    check.since = alerts
+                        .iter()
+                        .find(|a| a.name == check.name)
+                        .map(|a| a.since);
+                }
+            }
+            Err(e) => e.report(network, "readiness", "alert state not stored"),
+        }
+    }
+
+    async fn check_network(
+        network: &str,
+        client: &Client,
+        snapshots: &Snapshots,
+    ) -> NetworkReadiness {
+        let config = &client.config().readiness;
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let latest = tokio::time::timeout(timeout, latest_block(client))
+            .await
+            .unwrap_or(Err(PwError::Timeout(timeout)));
+
+        let (latest_block, block_age_ms) = match &latest {
+            Ok((number, timestamp)) => (Some(*number), Some(block_age_ms(*timestamp, now_ms()))),
+            Err(_) => (None, None),
+        };
+        let node = Check::new(
+            "node",
+            latest
+                .as_ref()
+                .map(|_| None)
+                .map_err(|e| format!("latest block not read: {}", e)),
+        );
+        let block = Check::new(
+            "block",
+            match block_age_ms {
+                Some(age) if age <= config.max_block_age_ms => Ok(Some(format!(
+                    "block {} mined {} ms ago",
+                    latest_block.unwrap_or_default(),
+                    age
+                ))),
+                Some(age) => Err(format!(
+                    "block {} mined {} ms ago, more than {} ms",
+                    latest_block.unwrap_or_default(),
+                    age,
+                    config.max_block_age_ms
+                )),
+                None => Err(String::from("latest block unknown")),
+            },
+        );
+
+        let invalid_abis = client.invalid_abis();
+        let abis = Check::new(
+            "abis",
+            if invalid_abis.is_empty() {
+                Ok(None)
+            } else {
+                Err(format!("not parsed: {}", invalid_abis.join(", ")))
+            },
+        );
+
+        let snapshot = snapshots.latest();
+        let snapshot_age_ms = snapshot.as_ref().map(|s| s.age().as_millis() as u64);
+        let snapshot = Check::new(
+            "snapshot",
+            match snapshot {
+                _ if !snapshots.is_enabled() => Ok(Some(String::from("disabled"))),
+                Some(snapshot) => Ok(Some(format!("block {}", snapshot.block))),
+                None => Err(String::from("no snapshot younger than max_age_ms")),
+            },
+        );
+
+        let checks = vec![node, block, abis, snapshot];
+        NetworkReadiness {
+            network: network.to_string(),
+            ready: checks.iter().all(|c| c.ok),
+            latest_block,
+            block_age_ms,
+            snapshot_age_ms,
+            checks,
+        }
+    }
+
+    // every network is probed at the same time
+    pub async fn check(&self) -> Readiness {
+        let networks = futures::future::join_all(self.networks.iter().map(
+            |(name, client, snapshots)| async move {
+                let mut network = Self::check_network(name, client, snapshots).await;
+                self.record_alerts(name, &mut network.checks).await;
+                network
+            },
+        ))
+        .await;
+        Readiness {
+            ready: networks.iter().all(|n| n.ready),
+            networks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_age() {
+        assert_eq!(block_age_ms(100, 100_500), 500);
+        assert_eq!(block_age_ms(101, 100_500), 0);
+    }
+
+    #[test]
+    fn test_check() {
+        let passed = Check::new("node", Ok(None));
+        assert!(passed.ok);
+        assert!(passed.message.is_none());
+
+        let failed = Check::new("block", Err(String::from("stale")));
+        assert!(!failed.ok);
+        assert_eq!(failed.message.as_deref(), Some("stale"));
+    }
+}
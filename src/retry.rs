@@ -0,0 +1,289 @@
+use std::time::{Duration, Instant};
+
+use futures::TryFuture;
+use futures_retry::{ErrorHandler, FutureFactory, FutureRetry, RetryPolicy};
+
+use rand::Rng;
+
+use crate::config::RetryConfig;
+use crate::error::{is_revert, PwError, Result};
+use crate::exporter;
+
+// JSON-RPC error codes nodes use for overload and rate limiting. -32603 is
+// left to the message, several nodes answer reverts with it
+const TRANSIENT_RPC_CODES: &[i64] = &[-32005, 429];
+
+const TRANSIENT_RPC_MESSAGES: &[&str] = &[
+    "timeout",
+    "timed out",
+    "rate limit",
+    "too many requests",
+    "header not found",
+    "internal error",
+    "try again",
+];
+
+// whether an error may go away when the same request is sent again
+pub trait Transient {
+    fn is_transient(&self) -> bool;
+}
+
+impl Transient for web3::Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            web3::Error::Unreachable
+            | web3::Error::Transport(_)
+            | web3::Error::InvalidResponse(_)
+            | web3::Error::Io(_) => true,
+            // a revert repeats whatever code the node answers it with
+            web3::Error::Rpc(_) if is_revert(self) => false,
+            web3::Error::Rpc(e) => {
+                let message = e.message.to_lowercase();
+                TRANSIENT_RPC_CODES.contains(&e.code.code())
+                    || TRANSIENT_RPC_MESSAGES.iter().any(|m| message.contains(m))
+            }
+            // reverts, bad ABI and decoding errors repeat on every attempt
+            _ => false,
+        }
+    }
+}
+
+impl Transient for web3::contract::Error {
+    fn is_transient(&self) -> bool {
+        match self {
+            web3::contract::Error::Api(e) => e.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+// errors of eth_getLogs, a block range the node refuses to serve (-32005 or a
+// message naming the limit) is refused again and left to the caller to shrink
+pub struct LogsError(pub web3::Error);
+
+impl LogsError {
+    pub fn is_range_limit(&self) -> bool {
+        match &self.0 {
+            web3::Error::Rpc(e) => {
+                let message = e.message.to_lowercase();
+                e.code.code() == -32005
+                    || message.contains("range")
+                    || message.contains("more than")
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Transient for LogsError {
+    fn is_transient(&self) -> bool {
+        !self.is_range_limit() && self.0.is_transient()
+    }
+}
+
+impl From<LogsError> for PwError {
+    fn from(e: LogsError) -> PwError {
+        PwError::Node(e.0)
+    }
+}
+
+// retries transient errors with exponential backoff and jitter until
+// `max_attempts` or the deadline is reached
+pub struct Retry {
+    config: RetryConfig,
+    started: Instant,
+}
+
+impl Retry {
+    pub fn new(config: &RetryConfig) -> Retry {
+        Retry {
+            config: config.clone(),
+            started: Instant::now(),
+        }
+    }
+
+    fn deadline(&self) -> Duration {
+        Duration::from_millis(self.config.deadline_ms)
+    }
+
+    // backoff before attempt `attempt + 1`, without jitter
+    fn backoff(&self, attempt: usize) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as usize) as i32;
+        let delay = self.config.initial_delay_ms as f64 * self.config.multiplier.powi(exponent);
+
+        Duration::from_millis(delay.min(self.config.max_delay_ms as f64) as u64)
+    }
+
+    fn delay(&self, attempt: usize) -> Duration {
+        let backoff = self.backoff(attempt);
+        if self.config.jitter == 0.0 {
+            return backoff;
+        }
+
+        let factor = rand::thread_rng().gen_range(-self.config.jitter..=self.config.jitter);
+        backoff.mul_f64(1.0 + factor)
+    }
+}
+
+impl<E: Transient> ErrorHandler<E> for Retry {
+    type OutError = E;
+
+    fn handle(&mut self, attempt: usize, e: E) -> RetryPolicy<E> {
+        if !e.is_transient() || attempt >= self.config.max_attempts {
+            return RetryPolicy::ForwardError(e);
+        }
+
+        let delay = self.delay(attempt);
+        if self.started.elapsed() + delay >= self.deadline() {
+            return RetryPolicy::ForwardError(e);
+        }
+        exporter::record_retry();
+        RetryPolicy::WaitRetry(delay)
+    }
+}
+
+// runs the future built by `factory` under `config`, a single attempt hanging
+// past the deadline fails the read with PwError::Timeout
+pub async fn retry<F, T, E>(config: &RetryConfig, factory: F) -> Result<T>
+where
+    F: FutureFactory,
+    F::FutureItem: TryFuture<Ok = T, Error = E>,
+    E: Transient + Into<PwError>,
+{
+    let policy = Retry::new(config);
+    let deadline = policy.deadline();
+
+    match tokio::time::timeout(deadline, FutureRetry::new(factory, policy)).await {
+        Ok(Ok((result, _))) => Ok(result),
+        Ok(Err((e, _))) => Err(e.into()),
+        Err(_) => Err(PwError::Timeout(deadline)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use web3::error::TransportError;
+
+    fn config() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            initial_delay_ms: 1,
+            max_delay_ms: 4,
+            multiplier: 2.0,
+            jitter: 0.0,
+            deadline_ms: 1000,
+        }
+    }
+
+    fn rpc_error(code: i64, message: &str) -> web3::Error {
+        let e = serde_json::json!({ "code": code, "message": message });
+        web3::Error::Rpc(serde_json::from_value(e).unwrap())
+    }
+
+    #[test]
+    fn test_classification() {
+        assert!(web3::Error::Unreachable.is_transient());
+        assert!(web3::Error::Transport(TransportError::Code(502)).is_transient());
+        assert!(rpc_error(-32005, "limit exceeded").is_transient());
+        assert!(rpc_error(-32000, "header not found").is_transient());
+        assert!(!rpc_error(3, "execution reverted").is_transient());
+        assert!(!rpc_error(-32603, "execution reverted").is_transient());
+        assert!(!rpc_error(-32000, "execution reverted: STF").is_transient());
+        assert!(rpc_error(-32603, "Internal error").is_transient());
+        assert!(!web3::Error::Decoder(String::from("bad data")).is_transient());
+
+        assert!(
+            !LogsError(rpc_error(-32005, "query returned more than 10000 results")).is_transient()
+        );
+        assert!(!LogsError(rpc_error(-32000, "block range is too wide")).is_transient());
+        assert!(LogsError(rpc_error(-32000, "header not found")).is_transient());
+        assert!(LogsError(web3::Error::Unreachable).is_transient());
+
+        assert!(web3::contract::Error::Api(web3::Error::Unreachable).is_transient());
+        assert!(!web3::contract::Error::InvalidOutputType(String::from("x")).is_transient());
+    }
+
+    #[test]
+    fn test_backoff() {
+        let retry = Retry::new(&config());
+
+        assert_eq!(retry.backoff(1), Duration::from_millis(1));
+        assert_eq!(retry.backoff(2), Duration::from_millis(2));
+        assert_eq!(retry.backoff(3), Duration::from_millis(4));
+        assert_eq!(retry.backoff(10), Duration::from_millis(4));
+
+        let retry = Retry::new(&RetryConfig {
+            initial_delay_ms: 100,
+            max_delay_ms: 1000,
+            jitter: 0.5,
+            ..config()
+        });
+        for _ in 0..100 {
+            let delay = retry.delay(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = retry(&config(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(web3::Error::Unreachable)
+        })
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(PwError::Node(web3::Error::Unreachable))
+        ));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_errors_are_not_retried() {
+        let attempts = AtomicUsize::new(0);
+        let result: Result<()> = retry(&config(), || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(rpc_error(3, "execution reverted"))
+        })
+        .await;
+
+        assert!(matches!(result, Err(PwError::Node(web3::Error::Rpc(_)))));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recovers_from_transient_errors() {
+        let attempts = AtomicUsize::new(0);
+        let result = retry(&config(), || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                Err(web3::Error::Unreachable)
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let config = RetryConfig {
+            deadline_ms: 20,
+            ..config()
+        };
+        let result: Result<()> = retry(&config, || async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            Ok::<(), web3::Error>(())
+        })
+        .await;
+
+        assert!(matches!(result, Err(PwError::Timeout(_))));
+    }
+}
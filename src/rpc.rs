@@ -1,9 +1,33 @@
+use std::future::Future;
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use actix_web::http::{header, header::*, StatusCode};
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpResponse, ResponseError};
 
 use serde_derive::{Deserialize, Serialize};
 
-use crate::client::Client;
+use utoipa::{IntoParams, OpenApi, ToSchema};
+use utoipa_swagger_ui::SwaggerUi;
+
+use web3::types::BlockId;
+
+use crate::candles::{Candle, Candles, Resolution};
+use crate::client::{
+    Asset, BlockTag, Client, FactoryPairs, PoolLiquidity, PoolPrice, PoolReserves, PoolShare, Quote,
+};
+use crate::error::PwError;
+use crate::events::IndexedEvent;
+use crate::exporter::{self, Exporter};
+use crate::history::{self, HistoryPoint};
+use crate::indexer::Indexer;
+use crate::jsonrpc::{self, Context};
+use crate::openapi;
+use crate::readiness::{Probe, Readiness};
+use crate::snapshot::{block_number, MetricFn, Snapshots};
+use crate::store::{ModelRecord, Store};
+use crate::trace::{self, Trace};
+use crate::twap::Twap;
+use crate::upstream::UpstreamStatus;
 
 fn apply_headers<B>(resp: &mut HttpResponse<B>) {
     resp.headers_mut().insert(
@@ -24,106 +48,450 @@ fn apply_headers<B>(resp: &mut HttpResponse<B>) {
     );
 }
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Response<T> {
     pub result: T,
+    // block the routes reading chain state read at and, when served from a
+    // snapshot, its age, for the events and candles routes the block events
+    // are indexed up to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub age_ms: Option<u64>,
+    // with `?meta=true` on the routes reading chain state
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
 }
 
-#[get("/rpc/base-price")]
-pub async fn get_wftm_price(client: web::Data<Client>) -> impl Responder {
-    let result = client.get_wftm_price().await;
+impl<T> Response<T> {
+    pub fn new(result: T) -> Response<T> {
+        Response {
+            result,
+            block: None,
+            age_ms: None,
+            meta: None,
+        }
+    }
+}
 
-    let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
-    );
-    apply_headers(&mut resp);
-    resp
+// where a result came from, so it can be checked against the chain
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+// the block read at and the snapshot age are the `block` and `ageMs` of the
+// response itself
+pub struct Meta {
+    // of the block, in seconds
+    pub timestamp: Option<u64>,
+    // hosts of the upstreams that answered, several with a quorum
+    pub upstreams: Vec<String>,
+    // contracts read
+    pub sources: Vec<String>,
 }
 
-#[get("/rpc/owned/base-pool-lps")]
-pub async fn get_wftm_gton_gc_pool_lp(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_wftm_gton_gc_pool_lp().await;
+impl Meta {
+    fn new(timestamp: Option<u64>, trace: &Trace) -> Meta {
+        Meta {
+            timestamp,
+            upstreams: trace.upstreams.iter().cloned().collect(),
+            sources: trace
+                .sources
+                .iter()
+                .map(|address| Client::encode_addr(*address))
+                .collect(),
+        }
+    }
+}
 
-    let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
-    );
-    apply_headers(&mut resp);
-    resp
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
 }
 
-#[get("/rpc/owned/usd-pool-lps")]
-pub async fn get_usdc_gton_gc_pool_lp(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_usdc_gton_gc_pool_lp().await;
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
 
-    let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
-    );
-    apply_headers(&mut resp);
-    resp
+impl ResponseError for PwError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            PwError::InvalidAddress(_)
+            | PwError::InvalidAmount(_)
+            | PwError::InvalidPath(_)
+            | PwError::InvalidRequest(_)
+            | PwError::TokenNotInPool(_, _) => StatusCode::BAD_REQUEST,
+            PwError::PairNotFound(_, _)
+            | PwError::BlockNotFound(_)
+            | PwError::NotIndexed(_)
+            | PwError::NoCandles(_) => StatusCode::NOT_FOUND,
+            PwError::NoQuote(_, _)
+            | PwError::DivisionByZero(_)
+            | PwError::NotFinite(_)
+            | PwError::Contract(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            PwError::Node(_) | PwError::NoQuorum(_, _) => StatusCode::BAD_GATEWAY,
+            PwError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            PwError::Io(_, _) | PwError::Store(_, _) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        exporter::record_error(self.code(), "http");
+        let body = ErrorResponse {
+            error: ErrorBody {
+                code: self.code().to_string(),
+                message: self.to_string(),
+            },
+        };
+
+        let mut resp =
+            HttpResponse::with_body(self.status_code(), serde_json::to_string(&body).unwrap());
+        apply_headers(&mut resp);
+        resp.map_into_boxed_body()
+    }
 }
 
-#[get("/rpc/base-liquidity")]
-pub async fn get_ftm_gton_liq(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_ftm_gton_liq().await;
+// registered once per configured network under `/rpc/{network}` and once
+// under `/rpc` for the default network
+pub fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(
+        web::QueryConfig::default()
+            .error_handler(|err, _| PwError::InvalidRequest(err.to_string()).into()),
+    )
+    .app_data(
+        web::PathConfig::default()
+            .error_handler(|err, _| PwError::InvalidRequest(err.to_string()).into()),
+    )
+    .service(get_wftm_price)
+    .service(get_wftm_gton_gc_pool_lp)
+    .service(get_usdc_gton_gc_pool_lp)
+    .service(get_ftm_gton_liq)
+    .service(get_usdc_gton_liq)
+    .service(get_ftm_gton_lp)
+    .service(get_usdc_gton_lp)
+    .service(get_gc_pol)
+    .service(get_pw_model_with_pol_mln)
+    .service(get_gc_pw_current_peg_usd)
+    .service(get_gc_pw_current_peg_ftm)
+    .service(get_gton_usdc_price)
+    .service(get_gton_wftm_price)
+    .service(get_pool_reserves)
+    .service(get_pool_spot_price)
+    .service(get_pool_liquidity)
+    .service(get_pool_lp_supply)
+    .service(get_pool_share)
+    .service(get_pool_events)
+    .service(get_candles)
+    .service(get_wftm_twap)
+    .service(get_gton_usdc_twap)
+    .service(get_gton_wftm_twap)
+    .service(get_tokens)
+    .service(get_token)
+    .service(get_factory_pairs)
+    .service(get_quote)
+    .service(get_quote_in)
+    .service(get_history)
+    .service(get_upstreams)
+    .service(get_model_history)
+    .service(post_jsonrpc);
+}
+
+// every route reading chain state accepts `?block=<number>` or
+// `?tag=latest|safe|finalized`, all reads of a request are pinned to that block
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BlockQueryArgs {
+    pub block: Option<u64>,
+    pub tag: Option<BlockTag>,
+    // adds `meta` to the response
+    #[serde(default)]
+    pub meta: bool,
+}
 
+fn json_response<T: serde::Serialize>(response: &Response<T>) -> HttpResponse<String> {
     let mut resp = HttpResponse::with_body(
         StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
+        serde_json::to_string(response).unwrap(),
     );
     apply_headers(&mut resp);
     resp
 }
 
-#[get("/rpc/usd-liquidity")]
-pub async fn get_usdc_gton_liq(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_usdc_gton_liq().await;
+// resolves the block of `block_args` and reads at it, `meta` is only filled
+// in when asked for since it costs a read of the block timestamp
+pub async fn block_result<T, F, Fut>(
+    block_args: &BlockQueryArgs,
+    client: &Client,
+    read: F,
+) -> Result<Response<T>, PwError>
+where
+    F: FnOnce(BlockId) -> Fut,
+    Fut: Future<Output = Result<T, PwError>>,
+{
+    let (result, trace) = trace::traced(async {
+        let block = client
+            .resolve_block(block_args.block, block_args.tag)
+            .await?;
+        let number = block_number(block);
+        let timestamp = async {
+            match number {
+                Some(number) if block_args.meta => {
+                    client.get_block_timestamp(number).await.map(Some)
+                }
+                _ => Ok(None),
+            }
+        };
+        let (result, timestamp) = tokio::try_join!(read(block), timestamp)?;
+        Ok::<_, PwError>((result, number, timestamp))
+    })
+    .await;
+    let (result, number, timestamp) = result?;
 
-    let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
-    );
-    apply_headers(&mut resp);
-    resp
+    Ok(Response {
+        block: number,
+        meta: block_args.meta.then(|| Meta::new(timestamp, &trace)),
+        ..Response::new(result)
+    })
 }
 
-#[get("/rpc/base-pool-lps")]
-pub async fn get_ftm_gton_lp(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_ftm_gton_lp().await;
+// `latest` reads are answered from the background snapshot while it is fresh,
+// explicit blocks and tags are computed on demand
+pub async fn metric_result<F, Fut>(
+    block_args: &BlockQueryArgs,
+    client: &Client,
+    snapshots: &Snapshots,
+    metric: MetricFn,
+    compute: F,
+) -> Result<Response<f64>, PwError>
+where
+    F: FnOnce(BlockId) -> Fut,
+    Fut: Future<Output = Result<f64, PwError>>,
+{
+    let response = match snapshots.latest() {
+        Some(snapshot)
+            if block_args.block.is_none()
+                && block_args.tag.unwrap_or_default() == BlockTag::Latest =>
+        {
+            Response {
+                result: metric(&snapshot.metrics),
+                block: Some(snapshot.block),
+                age_ms: Some(snapshot.age().as_millis() as u64),
+                meta: block_args
+                    .meta
+                    .then(|| Meta::new(Some(snapshot.timestamp), &snapshot.trace)),
+            }
+        }
+        _ => block_result(block_args, client, compute).await?,
+    };
+    Ok(response)
+}
 
-    let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
-    );
-    apply_headers(&mut resp);
-    resp
+async fn metric_response<F, Fut>(
+    block_args: &BlockQueryArgs,
+    client: &Client,
+    snapshots: &Snapshots,
+    metric: MetricFn,
+    compute: F,
+) -> Result<HttpResponse<String>, PwError>
+where
+    F: FnOnce(BlockId) -> Fut,
+    Fut: Future<Output = Result<f64, PwError>>,
+{
+    let response = metric_result(block_args, client, snapshots, metric, compute).await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/base-price")]
+pub async fn get_wftm_price(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.base_price,
+        |block| client.get_wftm_price(block),
+    )
+    .await
 }
 
-#[get("/rpc/gc-pol")]
-pub async fn get_gc_pol(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_gc_pol().await;
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/owned/base-pool-lps")]
+pub async fn get_wftm_gton_gc_pool_lp(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.owned_base_pool_lps,
+        |block| client.get_wftm_gton_gc_pool_lp(block),
+    )
+    .await
+}
 
-    let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
-    );
-    apply_headers(&mut resp);
-    resp
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/owned/usd-pool-lps")]
+pub async fn get_usdc_gton_gc_pool_lp(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.owned_usd_pool_lps,
+        |block| client.get_usdc_gton_gc_pool_lp(block),
+    )
+    .await
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/base-liquidity")]
+pub async fn get_ftm_gton_liq(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.base_liquidity,
+        |block| client.get_ftm_gton_liq(block),
+    )
+    .await
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/usd-liquidity")]
+pub async fn get_usdc_gton_liq(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.usd_liquidity,
+        |block| client.get_usdc_gton_liq(block),
+    )
+    .await
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/base-pool-lps")]
+pub async fn get_ftm_gton_lp(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.base_pool_lps,
+        |block| client.get_ftm_gton_lp(block),
+    )
+    .await
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/usd-pool-lps")]
+pub async fn get_usdc_gton_lp(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.usd_pool_lps,
+        |block| client.get_usdc_gton_lp(block),
+    )
+    .await
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/gc-pol")]
+pub async fn get_gc_pol(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.gc_pol,
+        |block| client.get_gc_pol(block),
+    )
+    .await
 }
 
 // @app.route('/rpc/pw-model-peg-with-pol-mln', methods=['GET'])
@@ -142,28 +510,36 @@ pub async fn get_gc_pol(client: web::Data<Client>) -> impl Responder {
 //     float(gcMaxL or 1)
 //   ))
 
-#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+// camelCase as in the original service, the snake_case names are accepted too
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 #[serde(rename_all = "camelCase")]
 pub struct PWModelQueryArgs {
     pub pol: f64,
-    #[serde(rename(serialize = "gcFloor"))]
+    #[serde(alias = "gc_floor")]
     pub gc_floor: f64,
-    #[serde(rename(serialize = "gcBias"))]
+    #[serde(alias = "gc_bias")]
     pub gc_bias: f64,
-    #[serde(rename(serialize = "gcMaxP"))]
+    #[serde(alias = "gc_max_p")]
     pub gc_max_p: f64,
-    #[serde(rename = "gcMaxL")]
+    #[serde(alias = "gc_max_l")]
     pub gc_max_l: f64,
 }
 
-#[get("/rpc/pw-model-peg-with-pol-mln")]
+#[utoipa::path(
+    params(PWModelQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/pw-model-peg-with-pol-mln")]
 pub async fn get_pw_model_with_pol_mln(
     payload: web::Query<PWModelQueryArgs>,
     client: web::Data<Client>,
-) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x)
+) -> Result<HttpResponse<String>, PwError> {
+    let result = client
         .get_pw_model_with_pol_mln(
             payload.pol,
             payload.gc_floor,
@@ -171,77 +547,836 @@ pub async fn get_pw_model_with_pol_mln(
             payload.gc_max_p,
             payload.gc_max_l,
         )
-        .await;
+        .await?;
 
     let mut resp = HttpResponse::with_body(
         StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
+        serde_json::to_string(&Response::new(result)).unwrap(),
     );
     apply_headers(&mut resp);
-    resp
+    Ok(resp)
+}
+
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/gc-current-peg-usd")]
+pub async fn get_gc_pw_current_peg_usd(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.gc_current_peg_usd,
+        |block| client.get_gc_pw_current_peg_usd(block),
+    )
+    .await
+}
+
+// @app.route('/rpc/gc-current-peg-base', methods=['GET'])
+// def getGCpwCurrentPegFTM():
+//   return wrap_result(pwcalc.getGCpwCurrentPegFTM())
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/gc-current-peg-base")]
+pub async fn get_gc_pw_current_peg_ftm(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.gc_current_peg_base,
+        |block| client.get_gc_pw_current_peg_ftm(block),
+    )
+    .await
+}
+
+// @app.route('/rpc/base-to-usdc-price', methods=['GET'])
+// def getGTONusdcPrice():
+//   return wrap_result(pwcalc.getGTONusdcPrice())
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/base-to-usdc-price")]
+pub async fn get_gton_usdc_price(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.base_to_usdc_price,
+        |block| client.get_gton_usdc_price(block),
+    )
+    .await
+}
+
+// @app.route('/rpc/base-to-quote-price', methods=['GET'])
+// def getGTONwftmPrice():
+//   return wrap_result(pwcalc.getGTONwftmPrice())
+#[utoipa::path(
+    params(BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/base-to-quote-price")]
+pub async fn get_gton_wftm_price(
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    metric_response(
+        &block_args,
+        &client,
+        &snapshots,
+        |m| m.base_to_quote_price,
+        |block| client.get_gton_wftm_price(block),
+    )
+    .await
+}
+
+#[utoipa::path(
+    params(("address" = String, Path), BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<PoolReserves>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/pool/{address}/reserves")]
+pub async fn get_pool_reserves(
+    address: web::Path<String>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_pool_reserves(address.as_str(), block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PoolPriceQueryArgs {
+    // token the price is quoted for, token0 of the pool if unset
+    pub base: Option<String>,
+}
+
+#[utoipa::path(
+    params(("address" = String, Path), PoolPriceQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<PoolPrice>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/pool/{address}/price")]
+pub async fn get_pool_spot_price(
+    address: web::Path<String>,
+    payload: web::Query<PoolPriceQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_pool_spot_price(address.as_str(), payload.base.as_deref(), block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(("address" = String, Path), BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<PoolLiquidity>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/pool/{address}/liquidity")]
+pub async fn get_pool_liquidity(
+    address: web::Path<String>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_pool_liquidity(address.as_str(), block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(("address" = String, Path), BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<f64>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/pool/{address}/lp-supply")]
+pub async fn get_pool_lp_supply(
+    address: web::Path<String>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_pool_lp_supply(address.as_str(), block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(("address" = String, Path), ("account" = String, Path), BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<PoolShare>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/pool/{address}/holders/{account}/share")]
+pub async fn get_pool_share(
+    path: web::Path<(String, String)>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let (address, account) = path.into_inner();
+    let response = block_result(&block_args, &client, |block| {
+        client.get_pool_share(address.as_str(), account.as_str(), block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+fn default_twap_window() -> u64 {
+    1800
+}
+
+fn default_events_limit() -> usize {
+    100
+}
+
+const MAX_EVENTS_LIMIT: usize = 1000;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PoolEventsQueryArgs {
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    // Sync, Swap, Mint, Burn or Transfer, every type if unset
+    #[serde(rename = "type")]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "default_events_limit")]
+    pub limit: usize,
+}
+
+// served from the local index, not read from the node
+pub async fn pool_events_result(
+    address: &str,
+    payload: &PoolEventsQueryArgs,
+    indexer: &Indexer,
+) -> Result<Response<Vec<IndexedEvent>>, PwError> {
+    let pool = Client::decode_addr(address)?;
+    if !indexer.is_indexed(pool) {
+        return Err(PwError::NotIndexed(address.to_string()));
+    }
+    let result: Vec<IndexedEvent> = indexer
+        .events(
+            pool,
+            payload.from_block.unwrap_or(0)..=payload.to_block.unwrap_or(u64::MAX),
+            payload.event_type.as_deref(),
+            payload.offset,
+            payload.limit.min(MAX_EVENTS_LIMIT),
+        )
+        .await?;
+    Ok(Response {
+        block: indexer.cursor(),
+        ..Response::new(result)
+    })
 }
 
-#[get("/rpc/gc-current-peg-usd")]
-pub async fn get_gc_pw_current_peg_usd(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_gc_pw_current_peg_usd().await;
+#[utoipa::path(
+    params(("address" = String, Path), PoolEventsQueryArgs),
+    responses(
+        (status = 200, body = Response<Vec<IndexedEvent>>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/pool/{address}/events")]
+pub async fn get_pool_events(
+    address: web::Path<String>,
+    payload: web::Query<PoolEventsQueryArgs>,
+    indexer: web::Data<Indexer>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = pool_events_result(address.as_str(), &payload, &indexer).await?;
+    Ok(json_response(&response))
+}
+
+fn default_candles_limit() -> usize {
+    500
+}
+
+const MAX_CANDLES_LIMIT: usize = 5000;
+
+// `from` and `to` bound the start of the candles, unix timestamps in seconds
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CandlesQueryArgs {
+    #[serde(default)]
+    pub resolution: Resolution,
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    #[serde(default = "default_candles_limit")]
+    pub limit: usize,
+}
+
+// built from the indexed Swap events of the base and usd pools
+pub fn candles_result(
+    pool: &str,
+    payload: &CandlesQueryArgs,
+    candles: &Candles,
+    indexer: &Indexer,
+) -> Result<Response<Vec<Candle>>, PwError> {
+    let address = Client::decode_addr(pool)?;
+    if !candles.has_candles(address) {
+        return Err(PwError::NoCandles(pool.to_string()));
+    }
+    if !indexer.is_indexed(address) {
+        return Err(PwError::NotIndexed(pool.to_string()));
+    }
+    let result: Vec<Candle> = candles.candles(
+        address,
+        payload.resolution,
+        payload.from.unwrap_or(0),
+        payload.to.unwrap_or(u64::MAX),
+        payload.limit.min(MAX_CANDLES_LIMIT),
+    );
+    Ok(Response {
+        block: candles.cursor(),
+        ..Response::new(result)
+    })
+}
+
+#[utoipa::path(
+    params(("pool" = String, Path), CandlesQueryArgs),
+    responses(
+        (status = 200, body = Response<Vec<Candle>>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "pools"
+)]
+#[get("/candles/{pool}")]
+pub async fn get_candles(
+    pool: web::Path<String>,
+    payload: web::Query<CandlesQueryArgs>,
+    candles: web::Data<Candles>,
+    indexer: web::Data<Indexer>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = candles_result(pool.as_str(), &payload, &candles, &indexer)?;
+    Ok(json_response(&response))
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TwapQueryArgs {
+    // seconds
+    #[serde(default = "default_twap_window")]
+    pub window: u64,
+}
+
+#[utoipa::path(
+    params(TwapQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<Twap>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "twap"
+)]
+#[get("/twap/base-price")]
+pub async fn get_wftm_twap(
+    payload: web::Query<TwapQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_wftm_twap(payload.window, block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(TwapQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<Twap>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "twap"
+)]
+#[get("/twap/base-to-usdc-price")]
+pub async fn get_gton_usdc_twap(
+    payload: web::Query<TwapQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_gton_usdc_twap(payload.window, block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(TwapQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<Twap>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "twap"
+)]
+#[get("/twap/base-to-quote-price")]
+pub async fn get_gton_wftm_twap(
+    payload: web::Query<TwapQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_gton_wftm_twap(payload.window, block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, body = Response<Vec<Asset>>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "tokens"
+)]
+#[get("/tokens")]
+pub async fn get_tokens(client: web::Data<Client>) -> Result<HttpResponse<String>, PwError> {
+    let result: Vec<Asset> = client.get_assets();
 
     let mut resp = HttpResponse::with_body(
         StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
+        serde_json::to_string(&Response::new(result)).unwrap(),
     );
     apply_headers(&mut resp);
-    resp
+    Ok(resp)
 }
 
-// @app.route('/rpc/gc-current-peg-base', methods=['GET'])
-// def getGCpwCurrentPegFTM():
-//   return wrap_result(pwcalc.getGCpwCurrentPegFTM())
-#[get("/rpc/gc-current-peg-base")]
-pub async fn get_gc_pw_current_peg_ftm(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_gc_pw_current_peg_ftm().await;
+#[utoipa::path(
+    params(("address" = String, Path), BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<Asset>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "tokens"
+)]
+#[get("/tokens/{address}")]
+pub async fn get_token(
+    address: web::Path<String>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_asset(address.as_str(), block)
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+fn default_pairs_limit() -> u64 {
+    50
+}
+
+pub const MAX_PAIRS_LIMIT: u64 = 500;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct FactoryPairsQueryArgs {
+    #[serde(default)]
+    pub offset: u64,
+    #[serde(default = "default_pairs_limit")]
+    pub limit: u64,
+}
+
+#[utoipa::path(
+    params(("address" = String, Path), FactoryPairsQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<FactoryPairs>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "tokens"
+)]
+#[get("/factory/{address}/pairs")]
+pub async fn get_factory_pairs(
+    address: web::Path<String>,
+    payload: web::Query<FactoryPairsQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = block_result(&block_args, &client, |block| {
+        client.get_factory_pairs(
+            address.as_str(),
+            payload.offset,
+            payload.limit.min(MAX_PAIRS_LIMIT),
+            block,
+        )
+    })
+    .await?;
+    Ok(json_response(&response))
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct QuoteQueryArgs {
+    // UniswapV2Router02, the router of the network config if unset
+    pub router: Option<String>,
+    // comma separated token addresses
+    pub path: String,
+    pub amount_in: Option<String>,
+    pub amount_out: Option<String>,
+}
+
+// `amount_in` is sold exactly when `exact_in`, `amount_out` is bought exactly otherwise
+pub async fn quote_result(
+    payload: &QuoteQueryArgs,
+    exact_in: bool,
+    block_args: &BlockQueryArgs,
+    client: &Client,
+) -> Result<Response<Quote>, PwError> {
+    let amount = if exact_in {
+        payload.amount_in.as_ref()
+    } else {
+        payload.amount_out.as_ref()
+    };
+    let path: Vec<&str> = payload.path.split(',').map(str::trim).collect();
+    let router = payload
+        .router
+        .as_deref()
+        .or_else(|| client.default_router())
+        .ok_or_else(|| PwError::InvalidRequest(String::from("router is required")))?;
+    let amount =
+        amount.ok_or_else(|| PwError::InvalidRequest(String::from("amount is required")))?;
+
+    block_result(block_args, client, |block| {
+        client.get_quote(router, &path, amount, exact_in, block)
+    })
+    .await
+}
+
+#[utoipa::path(
+    params(QuoteQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<Quote>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "quotes"
+)]
+#[get("/quote")]
+pub async fn get_quote(
+    payload: web::Query<QuoteQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = quote_result(&payload, true, &block_args, &client).await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    params(QuoteQueryArgs, BlockQueryArgs),
+    responses(
+        (status = 200, body = Response<Quote>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "quotes"
+)]
+#[get("/quote-in")]
+pub async fn get_quote_in(
+    payload: web::Query<QuoteQueryArgs>,
+    block_args: web::Query<BlockQueryArgs>,
+    client: web::Data<Client>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = quote_result(&payload, false, &block_args, &client).await?;
+    Ok(json_response(&response))
+}
+
+fn default_history_interval() -> u64 {
+    3600
+}
+
+// unix timestamps in seconds, the last day up to now if unset
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQueryArgs {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    #[serde(default = "default_history_interval")]
+    pub interval: u64,
+}
+
+pub async fn history_result(
+    metric: &str,
+    payload: &HistoryQueryArgs,
+    client: &Client,
+    snapshots: &Snapshots,
+) -> Result<Response<Vec<HistoryPoint>>, PwError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let to = payload.to.unwrap_or(now);
+    let from = payload.from.unwrap_or(to.saturating_sub(86400));
+    let result =
+        history::metric_history(client, snapshots, metric, from, to, payload.interval).await?;
+    Ok(Response::new(result))
+}
+
+#[utoipa::path(
+    params(("metric" = String, Path), HistoryQueryArgs),
+    responses(
+        (status = 200, body = Response<Vec<HistoryPoint>>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "metrics"
+)]
+#[get("/history/{metric:.*}")]
+pub async fn get_history(
+    metric: web::Path<String>,
+    payload: web::Query<HistoryQueryArgs>,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+) -> Result<HttpResponse<String>, PwError> {
+    let response = history_result(metric.as_str(), &payload, &client, &snapshots).await?;
+    Ok(json_response(&response))
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, body = Response<Vec<UpstreamStatus>>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "admin"
+)]
+#[get("/admin/upstreams")]
+pub async fn get_upstreams(client: web::Data<Client>) -> Result<HttpResponse<String>, PwError> {
+    let result: Vec<UpstreamStatus> = client.get_upstreams();
 
     let mut resp = HttpResponse::with_body(
         StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
+        serde_json::to_string(&Response::new(result)).unwrap(),
     );
     apply_headers(&mut resp);
-    resp
+    Ok(resp)
 }
 
-// @app.route('/rpc/base-to-usdc-price', methods=['GET'])
-// def getGTONusdcPrice():
-//   return wrap_result(pwcalc.getGTONusdcPrice())
-#[get("/rpc/base-to-usdc-price")]
-pub async fn get_gton_usdc_price(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_gton_usdc_price().await;
+// every set of model parameters the network was started with, oldest first
+#[utoipa::path(
+    responses(
+        (status = 200, body = Response<Vec<ModelRecord>>),
+        (status = "default", description = "error", body = ErrorResponse),
+    ),
+    tag = "admin"
+)]
+#[get("/admin/model-history")]
+pub async fn get_model_history(
+    client: web::Data<Client>,
+    store: web::Data<Store>,
+) -> Result<HttpResponse<String>, PwError> {
+    let result: Vec<ModelRecord> = store.model_history(&client.config().name).await?;
 
     let mut resp = HttpResponse::with_body(
         StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
+        serde_json::to_string(&Response::new(result)).unwrap(),
     );
     apply_headers(&mut resp);
+    Ok(resp)
+}
+
+// JSON-RPC 2.0 over every route above, see jsonrpc.rs
+#[utoipa::path(
+    request_body = serde_json::Value,
+    responses(
+        (status = 200, description = "a response or an array of them", body = serde_json::Value),
+        (status = 204, description = "only notifications were sent"),
+    ),
+    tag = "jsonrpc"
+)]
+#[post("")]
+pub async fn post_jsonrpc(
+    body: web::Bytes,
+    client: web::Data<Client>,
+    snapshots: web::Data<Snapshots>,
+    indexer: web::Data<Indexer>,
+    candles: web::Data<Candles>,
+    store: web::Data<Store>,
+) -> HttpResponse<String> {
+    let ctx = Context {
+        client: &client,
+        snapshots: &snapshots,
+        indexer: &indexer,
+        candles: &candles,
+        store: &store,
+    };
+    let mut resp = match jsonrpc::handle(&body, &ctx).await {
+        Some(response) => {
+            HttpResponse::with_body(StatusCode::OK, serde_json::to_string(&response).unwrap())
+        }
+        // only notifications were sent
+        None => HttpResponse::with_body(StatusCode::NO_CONTENT, String::new()),
+    };
+    apply_headers(&mut resp);
     resp
 }
 
-// @app.route('/rpc/base-to-quote-price', methods=['GET'])
-// def getGTONwftmPrice():
-//   return wrap_result(pwcalc.getGTONwftmPrice())
-#[get("/rpc/base-to-quote-price")]
-pub async fn get_gton_wftm_price(client: web::Data<Client>) -> impl Responder {
-    let client_l = Box::into_raw(Box::new(client));
-    let x = unsafe { Box::from_raw(client_l) };
-    let result = Box::leak(x).get_gton_wftm_price().await;
+// served once at the root, for every network
+pub fn root_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(get_metrics)
+        .service(get_healthz)
+        .service(get_readyz)
+        .service(SwaggerUi::new("/docs/{_:.*}").url("/openapi.json", openapi::ApiDoc::openapi()));
+}
 
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Prometheus text format", content_type = "text/plain", body = String),
+    ),
+    tag = "service"
+)]
+#[get("/metrics")]
+pub async fn get_metrics(exporter: web::Data<Exporter>) -> HttpResponse<String> {
+    let mut resp = HttpResponse::with_body(StatusCode::OK, exporter.render());
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; version=0.0.4"),
+    );
+    resp
+}
+
+// answers as long as the process serves requests, without touching the node
+#[utoipa::path(
+    responses((status = 200, body = Response<String>)),
+    tag = "service"
+)]
+#[get("/healthz")]
+pub async fn get_healthz() -> HttpResponse<String> {
     let mut resp = HttpResponse::with_body(
-        StatusCode::from_u16(200).unwrap(),
-        serde_json::to_string(&Response { result }).unwrap(),
+        StatusCode::OK,
+        serde_json::to_string(&Response::new("ok")).unwrap(),
     );
     apply_headers(&mut resp);
     resp
 }
+
+// 503 while any network fails a check, the body lists every check either way
+#[utoipa::path(
+    responses((status = 200, body = Readiness), (status = 503, body = Readiness)),
+    tag = "service"
+)]
+#[get("/readyz")]
+pub async fn get_readyz(probe: web::Data<Probe>) -> HttpResponse<String> {
+    let result: Readiness = probe.check().await;
+    let status = if result.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    let mut resp = HttpResponse::with_body(status, serde_json::to_string(&result).unwrap());
+    apply_headers(&mut resp);
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::TcpListener;
+
+    use actix_web::{test as actix_test, App, HttpServer};
+
+    use serde_json::{json, Value};
+
+    use crate::client::Props;
+    use crate::config::{Config, UpstreamConfig};
+
+    // a node with no contract deployed, every call returns no data
+    async fn empty_node(request: web::Json<Value>) -> HttpResponse {
+        let result = match request["method"].as_str() {
+            Some("eth_getCode") | Some("eth_call") => json!("0x"),
+            Some("eth_blockNumber") => json!("0x10"),
+            _ => Value::Null,
+        };
+        HttpResponse::Ok().json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+    }
+
+    #[actix_web::test]
+    async fn test_contract_error_status() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let node = format!("http://{}", listener.local_addr().unwrap());
+        let server = HttpServer::new(|| App::new().route("/", web::post().to(empty_node)))
+            .listen(listener)
+            .unwrap()
+            .workers(1)
+            .run();
+        actix_web::rt::spawn(server);
+
+        let client = Client::new(Props {
+            upstreams: vec![UpstreamConfig::new(&node)],
+            config: Config::from_file("./config.toml")
+                .unwrap()
+                .default_network()
+                .clone(),
+        })
+        .await
+        .unwrap();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(client))
+                .service(web::scope("/rpc").configure(routes)),
+        )
+        .await;
+
+        // the address is not a pair, so its reads don't decode
+        let request = actix_test::TestRequest::get()
+            .uri("/rpc/pool/0x000000000000000000000000000000000000dead/reserves?block=16")
+            .to_request();
+        let response = actix_test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = actix_test::read_body_json(response).await;
+        assert_eq!(body["error"]["code"], "contract_error");
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use web3::types::{BlockId, BlockNumber};
+
+use crate::client::Client;
+use crate::config::SnapshotConfig;
+use crate::error::{PwError, Result};
+use crate::store::Store;
+use crate::trace::{self, Trace};
+
+// every value served by the metric routes, computed at a single block
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metrics {
+    pub base_price: f64,
+    pub owned_base_pool_lps: f64,
+    pub owned_usd_pool_lps: f64,
+    pub base_liquidity: f64,
+    pub usd_liquidity: f64,
+    pub base_pool_lps: f64,
+    pub usd_pool_lps: f64,
+    pub gc_pol: f64,
+    pub gc_current_peg_usd: f64,
+    pub gc_current_peg_base: f64,
+    pub base_to_usdc_price: f64,
+    pub base_to_quote_price: f64,
+}
+
+pub type MetricFn = fn(&Metrics) -> f64;
+
+// every metric by the route it is served at, the history route takes the same names
+pub const METRICS: &[(&str, MetricFn)] = &[
+    ("base-price", |m| m.base_price),
+    ("owned/base-pool-lps", |m| m.owned_base_pool_lps),
+    ("owned/usd-pool-lps", |m| m.owned_usd_pool_lps),
+    ("base-liquidity", |m| m.base_liquidity),
+    ("usd-liquidity", |m| m.usd_liquidity),
+    ("base-pool-lps", |m| m.base_pool_lps),
+    ("usd-pool-lps", |m| m.usd_pool_lps),
+    ("gc-pol", |m| m.gc_pol),
+    ("gc-current-peg-usd", |m| m.gc_current_peg_usd),
+    ("gc-current-peg-base", |m| m.gc_current_peg_base),
+    ("base-to-usdc-price", |m| m.base_to_usdc_price),
+    ("base-to-quote-price", |m| m.base_to_quote_price),
+];
+
+pub fn metric(name: &str) -> Option<MetricFn> {
+    METRICS
+        .iter()
+        .find(|(metric, _)| *metric == name)
+        .map(|(_, get)| *get)
+}
+
+// a single metric of METRICS, without reading the others
+pub async fn compute_metric(client: &Client, name: &str, block: BlockId) -> Result<f64> {
+    match name {
+        "base-price" => client.get_wftm_price(block).await,
+        "owned/base-pool-lps" => client.get_wftm_gton_gc_pool_lp(block).await,
+        "owned/usd-pool-lps" => client.get_usdc_gton_gc_pool_lp(block).await,
+        "base-liquidity" => client.get_ftm_gton_liq(block).await,
+        "usd-liquidity" => client.get_usdc_gton_liq(block).await,
+        "base-pool-lps" => client.get_ftm_gton_lp(block).await,
+        "usd-pool-lps" => client.get_usdc_gton_lp(block).await,
+        "gc-pol" => client.get_gc_pol(block).await,
+        "gc-current-peg-usd" => client.get_gc_pw_current_peg_usd(block).await,
+        "gc-current-peg-base" => client.get_gc_pw_current_peg_ftm(block).await,
+        "base-to-usdc-price" => client.get_gton_usdc_price(block).await,
+        "base-to-quote-price" => client.get_gton_wftm_price(block).await,
+        _ => Err(PwError::InvalidRequest(format!(
+            "unknown metric {:?}",
+            name
+        ))),
+    }
+}
+
+impl Metrics {
+    pub async fn compute(client: &Client, block: BlockId) -> Result<Metrics> {
+        let (
+            base_price,
+            owned_base_pool_lps,
+            owned_usd_pool_lps,
+            base_liquidity,
+            usd_liquidity,
+            base_pool_lps,
+            usd_pool_lps,
+            (gc_pol, gc_current_peg_usd, gc_current_peg_base),
+            base_to_usdc_price,
+            base_to_quote_price,
+        ) = tokio::try_join!(
+            client.get_wftm_price(block),
+            client.get_wftm_gton_gc_pool_lp(block),
+            client.get_usdc_gton_gc_pool_lp(block),
+            client.get_ftm_gton_liq(block),
+            client.get_usdc_gton_liq(block),
+            client.get_ftm_gton_lp(block),
+            client.get_usdc_gton_lp(block),
+            // one read of the POL inputs for all three
+            client.get_gc_pol_and_pegs(block),
+            client.get_gton_usdc_price(block),
+            client.get_gton_wftm_price(block),
+        )?;
+
+        Ok(Metrics {
+            base_price,
+            owned_base_pool_lps,
+            owned_usd_pool_lps,
+            base_liquidity,
+            usd_liquidity,
+            base_pool_lps,
+            usd_pool_lps,
+            gc_pol,
+            gc_current_peg_usd,
+            gc_current_peg_base,
+            base_to_usdc_price,
+            base_to_quote_price,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub block: u64,
+    // of the block, in seconds
+    pub timestamp: u64,
+    pub taken_at: Instant,
+    pub metrics: Metrics,
+    // of the refresh, not kept in the database
+    pub trace: Trace,
+}
+
+impl Snapshot {
+    pub fn age(&self) -> Duration {
+        self.taken_at.elapsed()
+    }
+}
+
+// the latest snapshot of one network, replaced as a whole on every refresh,
+// and the history of earlier ones
+#[derive(Debug, Default)]
+pub struct Snapshots {
+    config: SnapshotConfig,
+    current: RwLock<Option<Arc<Snapshot>>>,
+    // oldest first, at most one every history_interval_ms
+    history: RwLock<VecDeque<Arc<Snapshot>>>,
+}
+
+// resolve_block always pins a block number
+pub fn block_number(block: BlockId) -> Option<u64> {
+    match block {
+        BlockId::Number(BlockNumber::Number(number)) => Some(number.as_u64()),
+        _ => None,
+    }
+}
+
+impl Snapshots {
+    pub fn new(config: &SnapshotConfig) -> Snapshots {
+        Snapshots {
+            config: config.clone(),
+            current: RwLock::new(None),
+            history: RwLock::new(VecDeque::new()),
+        }
+    }
+
+    // None while disabled, before the first refresh or once the snapshot is older than max_age_ms
+    pub fn latest(&self) -> Option<Arc<Snapshot>> {
+        let max_age = Duration::from_millis(self.config.max_age_ms);
+        self.current
+            .read()
+            .unwrap()
+            .clone()
+            .filter(|snapshot| self.config.enabled && snapshot.age() <= max_age)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    // seconds between two snapshots of the history
+    pub fn history_interval(&self) -> u64 {
+        self.config.history_interval_ms / 1000
+    }
+
+    // snapshots taken between the `from` and `to` timestamps, oldest first
+    pub fn history(&self, from: u64, to: u64) -> Vec<Arc<Snapshot>> {
+        self.history
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|snapshot| (from..=to).contains(&snapshot.timestamp))
+            .cloned()
+            .collect()
+    }
+
+    // the history of earlier runs, the newest history_size snapshots of the database
+    pub async fn restore(&self, store: &Store, network: &str) -> Result<()> {
+        if self.config.history_size == 0 {
+            return Ok(());
+        }
+        let snapshots = store
+            .load_snapshots(network, self.config.history_size)
+            .await?;
+        *self.history.write().unwrap() = snapshots.into_iter().map(Arc::new).collect();
+        Ok(())
+    }
+
+    // true when the snapshot was added to the history as well
+    pub fn store(&self, snapshot: Snapshot) -> bool {
+        let snapshot = Arc::new(snapshot);
+        *self.current.write().unwrap() = Some(snapshot.clone());
+
+        let mut history = self.history.write().unwrap();
+        let due = history.back().is_none_or(|last| {
+            snapshot.timestamp.saturating_sub(last.timestamp) * 1000
+                >= self.config.history_interval_ms
+        });
+        if !due || self.config.history_size == 0 {
+            return false;
+        }
+        history.push_back(snapshot);
+        while history.len() > self.config.history_size {
+            history.pop_front();
+        }
+        true
+    }
+
+    // snapshots added to the history are written to the database so a restart keeps them
+    async fn refresh(&self, client: &Client, store: &Store, network: &str) -> Result<()> {
+        let block = client.resolve_block(None, None).await?;
+        let number =
+            block_number(block).ok_or_else(|| PwError::BlockNotFound(String::from("latest")))?;
+        let current = self.current.read().unwrap().clone();
+        if current.is_some_and(|current| current.block >= number) {
+            return Ok(());
+        }
+
+        let (result, trace) = trace::traced(async {
+            tokio::try_join!(
+                Metrics::compute(client, block),
+                client.get_block_timestamp(number)
+            )
+        })
+        .await;
+        let (metrics, timestamp) = result?;
+        let snapshot = Snapshot {
+            block: number,
+            timestamp,
+            taken_at: Instant::now(),
+            metrics,
+            trace,
+        };
+        if self.store(snapshot.clone()) {
+            store.insert_snapshot(network, &snapshot).await?;
+        }
+        Ok(())
+    }
+
+    // recomputes the snapshot on every new block announced by the upstreams and
+    // at least every interval_ms, a failed refresh keeps the previous snapshot
+    pub fn spawn_refresher(
+        self: Arc<Self>,
+        client: Arc<Client>,
+        store: Arc<Store>,
+        network: String,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+        let interval = Duration::from_millis(self.config.interval_ms);
+
+        tokio::spawn(async move {
+            let mut heads = client.watch_heads();
+            loop {
+                if let Err(e) = self.refresh(&client, &store, &network).await {
+                    e.report(&network, "snapshot", "refresh failed");
+                }
+                if let Ok(Err(_)) = tokio::time::timeout(interval, heads.changed()).await {
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(block: u64, age: Duration) -> Snapshot {
+        Snapshot {
+            block,
+            timestamp: block * 60,
+            taken_at: Instant::now() - age,
+            metrics: Metrics {
+                base_price: 0.25,
+                ..Metrics::default()
+            },
+            trace: Trace::default(),
+        }
+    }
+
+    #[test]
+    fn test_latest() {
+        let snapshots = Snapshots::new(&SnapshotConfig::default());
+        assert!(snapshots.latest().is_none());
+
+        snapshots.store(snapshot(100, Duration::from_secs(1)));
+        let latest = snapshots.latest().unwrap();
+        assert_eq!(latest.block, 100);
+        assert_eq!(latest.metrics.base_price, 0.25);
+
+        snapshots.store(snapshot(101, Duration::from_secs(60)));
+        assert!(snapshots.latest().is_none());
+
+        let disabled = Snapshots::new(&SnapshotConfig {
+            enabled: false,
+            ..SnapshotConfig::default()
+        });
+        disabled.store(snapshot(100, Duration::ZERO));
+        assert!(disabled.latest().is_none());
+    }
+
+    #[test]
+    fn test_history() {
+        let snapshots = Snapshots::new(&SnapshotConfig {
+            history_size: 3,
+            ..SnapshotConfig::default()
+        });
+        // one a minute is kept
+        for block in [1, 2, 2, 3, 4, 5] {
+            snapshots.store(snapshot(block, Duration::ZERO));
+        }
+        let blocks: Vec<u64> = snapshots
+            .history(0, u64::MAX)
+            .iter()
+            .map(|s| s.block)
+            .collect();
+        assert_eq!(blocks, vec![3, 4, 5]);
+        assert_eq!(snapshots.history(180, 240).len(), 2);
+    }
+
+    #[test]
+    fn test_metric() {
+        let metrics = Metrics {
+            gc_pol: 3.0,
+            ..Metrics::default()
+        };
+        assert_eq!(metric("gc-pol").map(|get| get(&metrics)), Some(3.0));
+        assert!(metric("unknown").is_none());
+        assert_eq!(METRICS.len(), 12);
+    }
+
+    #[test]
+    fn test_block_number() {
+        assert_eq!(block_number(BlockId::Number(100.into())), Some(100));
+        assert_eq!(block_number(BlockId::Number(BlockNumber::Latest)), None);
+    }
+}
@@ -0,0 +1,564 @@
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use serde::{Deserialize, Serialize};
+
+use web3::types::Address;
+
+use utoipa::ToSchema;
+
+use crate::client::Client;
+use crate::config::ModelConfig;
+use crate::error::{PwError, Result};
+use crate::events::IndexedEvent;
+use crate::snapshot::{Metrics, Snapshot};
+use crate::trace::Trace;
+
+// applied in order on open, the number applied so far is kept in `user_version`,
+// append new migrations instead of editing released ones
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE snapshots (
+        network TEXT NOT NULL,
+        block INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        metrics TEXT NOT NULL,
+        PRIMARY KEY (network, block)
+    );
+    CREATE INDEX snapshots_timestamp ON snapshots (network, timestamp);
+
+    CREATE TABLE events (
+        network TEXT NOT NULL,
+        block INTEGER NOT NULL,
+        log_index INTEGER NOT NULL,
+        pool TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        type TEXT NOT NULL,
+        event TEXT NOT NULL,
+        PRIMARY KEY (network, block, log_index)
+    );
+    CREATE INDEX events_pool ON events (network, pool, block);
+
+    -- every event of the network up to `block` is in `events`
+    CREATE TABLE cursors (
+        network TEXT PRIMARY KEY,
+        block INTEGER NOT NULL
+    );
+
+    CREATE TABLE model_params (
+        network TEXT NOT NULL,
+        recorded_at INTEGER NOT NULL,
+        gc_floor REAL NOT NULL,
+        gc_bias REAL NOT NULL,
+        gc_max_p REAL NOT NULL,
+        gc_max_l REAL NOT NULL
+    );
+",
+    "
+    -- the readiness checks of every network, `since` is when `state` was entered
+    CREATE TABLE alerts (
+        network TEXT NOT NULL,
+        name TEXT NOT NULL,
+        state TEXT NOT NULL,
+        since INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (network, name)
+    );
+",
+];
+
+// model parameters a network ran with from `recorded_at` on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelRecord {
+    pub recorded_at: u64,
+    #[serde(flatten)]
+    pub model: ModelConfig,
+}
+
+// state of a named alert of a network and when it entered it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AlertRecord {
+    pub name: String,
+    pub state: String,
+    pub since: u64,
+    pub updated_at: u64,
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// SQLite database shared by every network, each row carries its network name
+pub struct Store {
+    path: String,
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    // an interrupted write is rolled back from the WAL by SQLite itself, so
+    // reopening after a crash recovers the last committed state
+    pub fn open(path: &str) -> Result<Store> {
+        let error = |e| PwError::Store(path.to_string(), e);
+        if let Some(dir) = Path::new(path)
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(dir).map_err(|e| PwError::Io(path.to_string(), e))?;
+        }
+
+        let mut conn = Connection::open(path).map_err(error)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))
+            .map_err(error)?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(error)?;
+        migrate(&mut conn).map_err(error)?;
+
+        Ok(Store {
+            path: path.to_string(),
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    // SQLite blocks, so statements run on the blocking thread pool
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let conn = self.conn.clone();
+        let result = tokio::task::spawn_blocking(move || f(&mut conn.lock().unwrap()))
+            .await
+            .unwrap();
+        result.map_err(|e| PwError::Store(self.path.clone(), e))
+    }
+
+    pub async fn insert_snapshot(&self, network: &str, snapshot: &Snapshot) -> Result<()> {
+        let (network, block, timestamp) = (network.to_string(), snapshot.block, snapshot.timestamp);
+        let metrics = serde_json::to_string(&snapshot.metrics).unwrap();
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO snapshots (network, block, timestamp, metrics)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![network, block, timestamp, metrics],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    // the newest `limit` snapshots, oldest first
+    pub async fn load_snapshots(&self, network: &str, limit: usize) -> Result<Vec<Snapshot>> {
+        let network = network.to_string();
+        let rows: Vec<(u64, u64, String)> = self
+            .run(move |conn| {
+                let mut statement = conn.prepare(
+                    "SELECT block, timestamp, metrics FROM snapshots WHERE network = ?1
+                     ORDER BY block DESC LIMIT ?2",
+                )?;
+                let rows = statement
+                    .query_map(params![network, limit as u64], |row| {
+                        Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                    })?
+                    .collect();
+                rows
+            })
+            .await?;
+
+        let now = unix_time();
+        Ok(rows
+            .into_iter()
+            .rev()
+            .filter_map(|(block, timestamp, metrics)| {
+                let metrics: Metrics = serde_json::from_str(&metrics).ok()?;
+                // the block timestamp stands in for when the snapshot was taken
+                let age = Duration::from_secs(now.saturating_sub(timestamp));
+                Some(Snapshot {
+                    block,
+                    timestamp,
+                    taken_at: Instant::now().checked_sub(age).unwrap_or_else(Instant::now),
+                    metrics,
+                    trace: Trace::default(),
+                })
+            })
+            .collect())
+    }
+
+    // events and the cursor are committed together, events stored before are kept
+    pub async fn insert_events(
+        &self,
+        network: &str,
+        events: &[IndexedEvent],
+        cursor: u64,
+    ) -> Result<()> {
+        let network = network.to_string();
+        let events = events.to_vec();
+        self.run(move |conn| {
+            let tx = conn.transaction()?;
+            {
+                let mut statement = tx.prepare(
+                    "INSERT OR IGNORE INTO events
+                     (network, block, log_index, pool, timestamp, type, event)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?;
+                for event in &events {
+                    statement.execute(params![
+                        network,
+                        event.block,
+                        event.log_index,
+                        Client::encode_addr(event.pool),
+                        event.timestamp,
+                        event.event.name(),
+                        serde_json::to_string(event).unwrap(),
+                    ])?;
+                }
+            }
+            tx.execute(
+                "INSERT OR REPLACE INTO cursors (network, block) VALUES (?1, ?2)",
+                params![network, cursor],
+            )?;
+            tx.commit()
+        })
+        .await
+    }
+
+    // every event of the network up to this block is stored
+    pub async fn event_cursor(&self, network: &str) -> Result<Option<u64>> {
+        let network = network.to_string();
+        self.run(move |conn| {
+            conn.query_row(
+                "SELECT block FROM cursors WHERE network = ?1",
+                params![network],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .await
+    }
+
+    // events of `pool` within `blocks` ordered by (block, log index), optionally
+    // of a single type, a page of at most `limit` from `offset` on
+    pub async fn events(
+        &self,
+        network: &str,
+        pool: Address,
+        blocks: RangeInclusive<u64>,
+        name: Option<&str>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<IndexedEvent>> {
+        let (network, name) = (network.to_string(), name.map(str::to_string));
+        // SQLite integers are signed
+        let int = |n: u64| n.min(i64::MAX as u64) as i64;
+        let (from, to) = (int(*blocks.start()), int(*blocks.end()));
+        let (offset, limit) = (int(offset as u64), int(limit as u64));
+        let rows: Vec<String> = self
+            .run(move |conn| {
+                let mut statement = conn.prepare_cached(
+                    "SELECT event FROM events
+                     WHERE network = ?1 AND pool = ?2 AND block BETWEEN ?3 AND ?4
+                     AND (?5 IS NULL OR type = ?5 COLLATE NOCASE)
+                     ORDER BY block, log_index LIMIT ?6 OFFSET ?7",
+                )?;
+                let rows = statement
+                    .query_map(
+                        params![
+                            network,
+                            Client::encode_addr(pool),
+                            from,
+                            to,
+                            name,
+                            limit,
+                            offset
+                        ],
+                        |row| row.get(0),
+                    )?
+                    .collect();
+                rows
+            })
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(|event| serde_json::from_str(event).ok())
+            .collect())
+    }
+
+    // adds a record when the parameters differ from the last ones recorded
+    pub async fn record_model(&self, network: &str, model: &ModelConfig) -> Result<()> {
+        let network = network.to_string();
+        let model = model.clone();
+        self.run(move |conn| {
+            let last: Option<(f64, f64, f64, f64)> = conn
+                .query_row(
+                    "SELECT gc_floor, gc_bias, gc_max_p, gc_max_l FROM model_params
+                     WHERE network = ?1 ORDER BY recorded_at DESC, rowid DESC LIMIT 1",
+                    params![network],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+                )
+                .optional()?;
+            let current = (
+                model.gc_floor,
+                model.gc_bias,
+                model.gc_max_p,
+                model.gc_max_l,
+            );
+            if last == Some(current) {
+                return Ok(());
+            }
+            conn.execute(
+                "INSERT INTO model_params
+                 (network, recorded_at, gc_floor, gc_bias, gc_max_p, gc_max_l)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    network,
+                    unix_time(),
+                    model.gc_floor,
+                    model.gc_bias,
+                    model.gc_max_p,
+                    model.gc_max_l
+                ],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    // oldest first
+    pub async fn model_history(&self, network: &str) -> Result<Vec<ModelRecord>> {
+        let network = network.to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT recorded_at, gc_floor, gc_bias, gc_max_p, gc_max_l FROM model_params
+                 WHERE network = ?1 ORDER BY recorded_at, rowid",
+            )?;
+            let records = statement
+                .query_map(params![network], |row| {
+                    Ok(ModelRecord {
+                        recorded_at: row.get(0)?,
+                        model: ModelConfig {
+                            gc_floor: row.get(1)?,
+                            gc_bias: row.get(2)?,
+                            gc_max_p: row.get(3)?,
+                            gc_max_l: row.get(4)?,
+                        },
+                    })
+                })?
+                .collect();
+            records
+        })
+        .await
+    }
+
+    // `since` is kept while the state doesn't change
+    pub async fn set_alert(&self, network: &str, name: &str, state: &str) -> Result<()> {
+        let (network, name, state) = (network.to_string(), name.to_string(), state.to_string());
+        self.run(move |conn| {
+            conn.execute(
+                "INSERT INTO alerts (network, name, state, since, updated_at)
+                 VALUES (?1, ?2, ?3, ?4, ?4)
+                 ON CONFLICT (network, name) DO UPDATE SET
+                    since = CASE WHEN state = excluded.state THEN since ELSE excluded.since END,
+                    state = excluded.state,
+                    updated_at = excluded.updated_at",
+                params![network, name, state, unix_time()],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    pub async fn alerts(&self, network: &str) -> Result<Vec<AlertRecord>> {
+        let network = network.to_string();
+        self.run(move |conn| {
+            let mut statement = conn.prepare(
+                "SELECT name, state, since, updated_at FROM alerts WHERE network = ?1
+                 ORDER BY name",
+            )?;
+            let alerts = statement
+                .query_map(params![network], |row| {
+                    Ok(AlertRecord {
+                        name: row.get(0)?,
+                        state: row.get(1)?,
+                        since: row.get(2)?,
+                        updated_at: row.get(3)?,
+                    })
+                })?
+                .collect();
+            alerts
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use web3::types::H256;
+
+    use crate::events::PairEvent;
+
+    fn store(name: &str) -> (Store, String) {
+        let path = std::env::temp_dir().join(format!("pw-rs-test-{}.db", name));
+        let path = path.to_str().unwrap().to_string();
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+        }
+        (Store::open(&path).unwrap(), path)
+    }
+
+    fn event(block: u64, log_index: u64) -> IndexedEvent {
+        IndexedEvent {
+            pool: Address::repeat_byte(0xaa),
+            block,
+            timestamp: block * 2,
+            transaction_hash: H256::zero(),
+            log_index,
+            event: PairEvent::Sync {
+                reserve0: 1.into(),
+                reserve1: 2.into(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_migrations() {
+        let (store, path) = store("migrations");
+        drop(store);
+        // reopening applies nothing twice
+        let store = Store::open(&path).unwrap();
+        let version: usize = store
+            .conn
+            .lock()
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let (store, path) = store("events");
+        store
+            .insert_events("fantom", &[event(10, 1), event(10, 0)], 12)
+            .await
+            .unwrap();
+        store
+            .insert_events("fantom", &[event(10, 1), event(14, 0)], 15)
+            .await
+            .unwrap();
+        store.insert_events("other", &[], 3).await.unwrap();
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+        let pool = Address::repeat_byte(0xaa);
+        let events = store
+            .events("fantom", pool, 0..=u64::MAX, None, 0, usize::MAX)
+            .await
+            .unwrap();
+        let keys: Vec<(u64, u64)> = events.iter().map(IndexedEvent::key).collect();
+        assert_eq!(keys, vec![(10, 0), (10, 1), (14, 0)]);
+        assert_eq!(events[2], event(14, 0));
+        assert_eq!(store.event_cursor("fantom").await.unwrap(), Some(15));
+
+        let page = store
+            .events("fantom", pool, 10..=14, Some("sync"), 1, 1)
+            .await
+            .unwrap();
+        assert_eq!(page, vec![event(10, 1)]);
+        assert!(store
+            .events("fantom", pool, 0..=100, Some("Swap"), 0, 100)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(store
+            .events("other", pool, 0..=100, None, 0, 100)
+            .await
+            .unwrap()
+            .is_empty());
+        assert_eq!(store.event_cursor("other").await.unwrap(), Some(3));
+        assert_eq!(store.event_cursor("none").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots() {
+        let (store, _) = store("snapshots");
+        for block in 1..=5 {
+            let snapshot = Snapshot {
+                block,
+                timestamp: block * 60,
+                taken_at: Instant::now(),
+                metrics: Metrics {
+                    gc_pol: block as f64,
+                    ..Metrics::default()
+                },
+                trace: Trace::default(),
+            };
+            store.insert_snapshot("fantom", &snapshot).await.unwrap();
+        }
+
+        let snapshots = store.load_snapshots("fantom", 3).await.unwrap();
+        let blocks: Vec<u64> = snapshots.iter().map(|s| s.block).collect();
+        assert_eq!(blocks, vec![3, 4, 5]);
+        assert_eq!(snapshots[2].metrics.gc_pol, 5.0);
+        assert!(store.load_snapshots("other", 3).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_model_history() {
+        let (store, _) = store("model");
+        let model = ModelConfig::default();
+        store.record_model("fantom", &model).await.unwrap();
+        store.record_model("fantom", &model).await.unwrap();
+        let changed = ModelConfig {
+            gc_floor: 3.0,
+            ..ModelConfig::default()
+        };
+        store.record_model("fantom", &changed).await.unwrap();
+
+        let history = store.model_history("fantom").await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].model, model);
+        assert_eq!(history[1].model, changed);
+    }
+
+    #[tokio::test]
+    async fn test_alerts() {
+        let (store, path) = store("alerts");
+        store.set_alert("fantom", "peg", "failing").await.unwrap();
+        let since = store.alerts("fantom").await.unwrap()[0].since;
+        store.set_alert("fantom", "peg", "failing").await.unwrap();
+        store.set_alert("fantom", "lag", "ok").await.unwrap();
+        drop(store);
+
+        let store = Store::open(&path).unwrap();
+        let alerts = store.alerts("fantom").await.unwrap();
+        let states: Vec<(&str, &str)> = alerts
+            .iter()
+            .map(|a| (a.name.as_str(), a.state.as_str()))
+            .collect();
+        assert_eq!(states, vec![("lag", "ok"), ("peg", "failing")]);
+        assert_eq!(alerts[1].since, since);
+        assert!(store.alerts("other").await.unwrap().is_empty());
+    }
+}
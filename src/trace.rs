@@ -0,0 +1,78 @@
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::future::Future;
+
+use web3::types::Address;
+
+// what the node reads of one computation went through, collected in a task
+// local so the client and the transports need no extra arguments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trace {
+    // hosts of the upstreams that answered
+    pub upstreams: BTreeSet<String>,
+    // contracts read
+    pub sources: BTreeSet<Address>,
+}
+
+impl Trace {
+    fn merge(&mut self, other: &Trace) {
+        self.upstreams.extend(other.upstreams.iter().cloned());
+        self.sources.extend(other.sources.iter().copied());
+    }
+}
+
+tokio::task_local! {
+    static TRACE: RefCell<Trace>;
+}
+
+// runs `future` and returns what it read, an enclosing trace sees the reads too
+pub async fn traced<F: Future>(future: F) -> (F::Output, Trace) {
+    let (output, trace) = TRACE
+        .scope(RefCell::new(Trace::default()), async {
+            let output = future.await;
+            (output, TRACE.with(|trace| trace.take()))
+        })
+        .await;
+    let _ = TRACE.try_with(|outer| outer.borrow_mut().merge(&trace));
+    (output, trace)
+}
+
+// no-ops outside of `traced`
+pub fn record_upstream(host: String) {
+    let _ = TRACE.try_with(|trace| trace.borrow_mut().upstreams.insert(host));
+}
+
+pub fn record_sources(sources: impl IntoIterator<Item = Address>) {
+    let _ = TRACE.try_with(|trace| trace.borrow_mut().sources.extend(sources));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_traced() {
+        record_upstream(String::from("https://ignored"));
+
+        let ((), outer) = traced(async {
+            record_sources([Address::repeat_byte(1)]);
+            let ((), inner) = traced(async {
+                record_upstream(String::from("https://rpc.ftm.tools"));
+                record_sources([Address::repeat_byte(2), Address::repeat_byte(1)]);
+            })
+            .await;
+            assert_eq!(inner.sources.len(), 2);
+            tokio::task::yield_now().await;
+        })
+        .await;
+
+        assert_eq!(
+            outer.upstreams,
+            BTreeSet::from([String::from("https://rpc.ftm.tools")])
+        );
+        assert_eq!(
+            outer.sources,
+            BTreeSet::from([Address::repeat_byte(1), Address::repeat_byte(2)])
+        );
+    }
+}
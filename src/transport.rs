@@ -0,0 +1,138 @@
+use futures::future::{BoxFuture, FutureExt};
+use futures::stream::{BoxStream, StreamExt};
+
+use jsonrpc_core::Call;
+
+use serde_json::Value;
+
+use web3::{
+    api::SubscriptionId, error::TransportError, transports, DuplexTransport, RequestId, Transport,
+};
+
+// connection to a single node, the kind is picked from the url scheme
+#[derive(Debug, Clone)]
+pub enum NodeTransport {
+    Http(transports::Http),
+    WebSocket(transports::WebSocket),
+    Ipc(transports::Ipc),
+}
+
+// `ipc://<path>` or a bare absolute path
+fn ipc_path(url: &str) -> Option<&str> {
+    url.strip_prefix("ipc://")
+        .or_else(|| url.starts_with('/').then_some(url))
+}
+
+pub fn kind(url: &str) -> &'static str {
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        "websocket"
+    } else if ipc_path(url).is_some() {
+        "ipc"
+    } else {
+        "http"
+    }
+}
+
+// whether the node at `url` can push subscriptions
+pub fn is_duplex(url: &str) -> bool {
+    kind(url) != "http"
+}
+
+impl NodeTransport {
+    pub async fn connect(url: &str) -> web3::Result<NodeTransport> {
+        match kind(url) {
+            "websocket" => Ok(NodeTransport::WebSocket(
+                transports::WebSocket::new(url).await?,
+            )),
+            "ipc" => Self::connect_ipc(ipc_path(url).unwrap_or(url)).await,
+            _ => Ok(NodeTransport::Http(transports::Http::new(url)?)),
+        }
+    }
+
+    #[cfg(unix)]
+    async fn connect_ipc(path: &str) -> web3::Result<NodeTransport> {
+        Ok(NodeTransport::Ipc(transports::Ipc::new(path).await?))
+    }
+
+    #[cfg(not(unix))]
+    async fn connect_ipc(_path: &str) -> web3::Result<NodeTransport> {
+        Err(web3::Error::Transport(TransportError::Message(
+            String::from("IPC is only available on Unix"),
+        )))
+    }
+}
+
+impl Transport for NodeTransport {
+    type Out = BoxFuture<'static, web3::Result<Value>>;
+
+    fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+        match self {
+            NodeTransport::Http(t) => t.prepare(method, params),
+            NodeTransport::WebSocket(t) => t.prepare(method, params),
+            NodeTransport::Ipc(t) => t.prepare(method, params),
+        }
+    }
+
+    fn send(&self, id: RequestId, request: Call) -> Self::Out {
+        match self {
+            NodeTransport::Http(t) => t.send(id, request).boxed(),
+            NodeTransport::WebSocket(t) => t.send(id, request).boxed(),
+            NodeTransport::Ipc(t) => t.send(id, request).boxed(),
+        }
+    }
+}
+
+impl DuplexTransport for NodeTransport {
+    type NotificationStream = BoxStream<'static, Value>;
+
+    fn subscribe(&self, id: SubscriptionId) -> web3::Result<Self::NotificationStream> {
+        match self {
+            NodeTransport::Http(_) => Err(web3::Error::Transport(TransportError::Message(
+                String::from("subscriptions need a websocket or IPC connection"),
+            ))),
+            NodeTransport::WebSocket(t) => Ok(t.subscribe(id)?.boxed()),
+            NodeTransport::Ipc(t) => Ok(t.subscribe(id)?.boxed()),
+        }
+    }
+
+    fn unsubscribe(&self, id: SubscriptionId) -> web3::Result<()> {
+        match self {
+            NodeTransport::Http(_) => Ok(()),
+            NodeTransport::WebSocket(t) => t.unsubscribe(id),
+            NodeTransport::Ipc(t) => t.unsubscribe(id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(kind("https://rpc.fantom.network"), "http");
+        assert_eq!(kind("wss://wsapi.fantom.network"), "websocket");
+        assert_eq!(kind("ipc:///var/run/opera.ipc"), "ipc");
+        assert_eq!(kind("/var/run/opera.ipc"), "ipc");
+        assert_eq!(
+            ipc_path("ipc:///var/run/opera.ipc"),
+            Some("/var/run/opera.ipc")
+        );
+        assert!(!is_duplex("http://localhost:8545"));
+    }
+
+    #[tokio::test]
+    async fn test_http_has_no_subscriptions() {
+        let transport = NodeTransport::connect("http://localhost:8545")
+            .await
+            .unwrap();
+        assert!(matches!(transport, NodeTransport::Http(_)));
+        assert!(transport
+            .subscribe(SubscriptionId::from(String::from("0x1")))
+            .is_err());
+
+        assert!(NodeTransport::connect("ipc:///nonexistent/pw-rs.ipc")
+            .await
+            .is_err());
+    }
+}
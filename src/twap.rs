@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, ToPrimitive};
+
+use serde::{Deserialize, Serialize};
+
+use utoipa::ToSchema;
+
+use web3::types::U256;
+
+// UniswapV2 accumulates prices as UQ112x112 fixed point numbers
+const RESOLUTION: i32 = 112;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Twap {
+    pub pool: String,
+    pub base: String,
+    pub quote: String,
+    pub price: f64,
+    pub window: u64,
+    pub from_block: u64,
+    pub to_block: u64,
+    pub from_timestamp: u64,
+    pub to_timestamp: u64,
+}
+
+// price{0,1}CumulativeLast of a pair as seen at `block`, brought up to the
+// block timestamp the same way UniswapV2OracleLibrary.currentCumulativePrices does
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct CumulativePrices {
+    pub block: u64,
+    pub timestamp: u64,
+    pub price0_cumulative: U256,
+    pub price1_cumulative: U256,
+}
+
+impl CumulativePrices {
+    pub fn new(
+        block: u64,
+        timestamp: u64,
+        price0_cumulative_last: U256,
+        price1_cumulative_last: U256,
+        reserves: (U256, U256, u32),
+    ) -> CumulativePrices {
+        let (reserve0, reserve1, block_timestamp_last) = reserves;
+
+        let mut price0_cumulative = price0_cumulative_last;
+        let mut price1_cumulative = price1_cumulative_last;
+
+        // timestamps are stored mod 2**32 by the pair, subtraction overflow is desired
+        let elapsed = (timestamp as u32).wrapping_sub(block_timestamp_last);
+        if elapsed > 0 && !reserve0.is_zero() && !reserve1.is_zero() {
+            let elapsed = U256::from(elapsed);
+            price0_cumulative = price0_cumulative
+                .overflowing_add(
+                    ((reserve1 << RESOLUTION) / reserve0)
+                        .overflowing_mul(elapsed)
+                        .0,
+                )
+                .0;
+            price1_cumulative = price1_cumulative
+                .overflowing_add(
+                    ((reserve0 << RESOLUTION) / reserve1)
+                        .overflowing_mul(elapsed)
+                        .0,
+                )
+                .0;
+        }
+
+        CumulativePrices {
+            block,
+            timestamp,
+            price0_cumulative,
+            price1_cumulative,
+        }
+    }
+}
+
+fn average_price(start: U256, end: U256, elapsed: u64) -> f64 {
+    // cumulative prices are meant to overflow, only the difference matters
+    let delta = end.overflowing_sub(start).0 / U256::from(elapsed);
+
+    let r = BigDecimal::from_str(delta.to_string().as_str()).unwrap();
+    let r = r.to_f64().unwrap();
+
+    r / 2f64.powi(RESOLUTION)
+}
+
+// average prices of token0 in token1 and of token1 in token0 between two
+// observations, not yet adjusted for token decimals, None if no time passed
+pub fn average_prices(start: &CumulativePrices, end: &CumulativePrices) -> Option<(f64, f64)> {
+    if end.timestamp <= start.timestamp {
+        return None;
+    }
+    let elapsed = end.timestamp - start.timestamp;
+
+    Some((
+        average_price(start.price0_cumulative, end.price0_cumulative, elapsed),
+        average_price(start.price1_cumulative, end.price1_cumulative, elapsed),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q112(v: u64) -> U256 {
+        U256::from(v) << RESOLUTION
+    }
+
+    #[test]
+    fn test_counterfactual_accumulation() {
+        // reserves 1:4 and 100 seconds since the last sync
+        let observation = CumulativePrices::new(
+            10,
+            1100,
+            U256::zero(),
+            U256::zero(),
+            (U256::from(1000), U256::from(4000), 1000),
+        );
+
+        assert_eq!(observation.price0_cumulative, q112(4) * U256::from(100));
+        assert_eq!(
+            observation.price1_cumulative,
+            (q112(1) / 4) * U256::from(100)
+        );
+    }
+
+    #[test]
+    fn test_no_accumulation_in_same_second() {
+        let observation = CumulativePrices::new(
+            10,
+            1000,
+            q112(7),
+            q112(3),
+            (U256::from(1000), U256::from(4000), 1000),
+        );
+
+        assert_eq!(observation.price0_cumulative, q112(7));
+        assert_eq!(observation.price1_cumulative, q112(3));
+    }
+
+    #[test]
+    fn test_average_prices() {
+        let start = CumulativePrices {
+            block: 1,
+            timestamp: 0,
+            price0_cumulative: q112(10),
+            price1_cumulative: q112(1),
+        };
+        // price0 of 2 for 5 seconds, price1 of 0.5 for 5 seconds
+        let end = CumulativePrices {
+            block: 2,
+            timestamp: 5,
+            price0_cumulative: q112(20),
+            price1_cumulative: q112(1) + q112(5) / 2,
+        };
+
+        assert_eq!(average_prices(&start, &end), Some((2.0, 0.5)));
+        assert_eq!(average_prices(&end, &end), None);
+    }
+
+    #[test]
+    fn test_average_prices_overflow() {
+        let start = CumulativePrices {
+            block: 1,
+            timestamp: 0,
+            price0_cumulative: U256::MAX - q112(1) + 1,
+            price1_cumulative: U256::zero(),
+        };
+        let end = CumulativePrices {
+            block: 2,
+            timestamp: 2,
+            price0_cumulative: q112(5),
+            price1_cumulative: q112(1),
+        };
+
+        assert_eq!(average_prices(&start, &end).unwrap().0, 3.0);
+    }
+}